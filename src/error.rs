@@ -1,13 +1,16 @@
 use std::sync::PoisonError;
 
 use bincode::ErrorKind;
+use serde::{Deserialize, Serialize};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Error {
     Parse(String),
     Internal(String),
+    // A concurrent transaction wrote the same key. The transaction should be retried.
+    WriteConflict,
 }
 
 impl From<std::num::ParseIntError> for Error {
@@ -34,6 +37,12 @@ impl From<Box<ErrorKind>> for Error {
     }
 }
 
+impl From<serde_json::Error> for Error {
+    fn from(value: serde_json::Error) -> Self {
+        Error::Internal(value.to_string())
+    }
+}
+
 impl From<std::io::Error> for Error {
     fn from(value: std::io::Error) -> Self {
         Error::Internal(value.to_string())
@@ -1,3 +1,4 @@
 pub mod sql;
 pub mod error;
 pub mod storage;
+pub mod server;
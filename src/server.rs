@@ -0,0 +1,192 @@
+use std::{
+    io::{BufReader, BufWriter, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    thread,
+};
+
+use crate::{
+    error::{Error, Result},
+    sql::{engine::Engine, executor::ResultSet},
+};
+
+// Largest frame read or written, so that a bad length can't make a peer allocate up to
+// 4 GiB for a single frame.
+const MAX_FRAME_SIZE: usize = 256 * 1024 * 1024;
+
+// Every message on the wire is a big-endian u32 length followed by that many bytes.
+// Requests carry the SQL text, responses a bincode-encoded Result<ResultSet>.
+fn write_frame(writer: &mut impl Write, buf: &[u8]) -> Result<()> {
+    check_frame_size(buf.len())?;
+    writer.write_all(&(buf.len() as u32).to_be_bytes())?;
+    writer.write_all(buf)?;
+    writer.flush()?;
+    Ok(())
+}
+
+fn read_frame(reader: &mut impl Read) -> Result<Option<Vec<u8>>> {
+    let mut len_buf = [0; 4];
+    match reader.read_exact(&mut len_buf) {
+        Ok(()) => {}
+        Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err.into()),
+    }
+
+    let len = u32::from_be_bytes(len_buf) as usize;
+    check_frame_size(len)?;
+    let mut buf = vec![0; len];
+    reader.read_exact(&mut buf)?;
+    Ok(Some(buf))
+}
+
+fn check_frame_size(len: usize) -> Result<()> {
+    if len > MAX_FRAME_SIZE {
+        return Err(Error::Internal(format!(
+            "frame of {} bytes exceeds the maximum of {} bytes",
+            len, MAX_FRAME_SIZE
+        )));
+    }
+    Ok(())
+}
+
+pub struct Server<E: Engine> {
+    engine: E,
+    listener: TcpListener,
+}
+
+impl<E: Engine + Send + 'static> Server<E> {
+    pub fn bind(engine: E, addr: impl ToSocketAddrs) -> Result<Self> {
+        Ok(Self {
+            engine,
+            listener: TcpListener::bind(addr)?,
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    // Accepts connections forever, serving each one on its own thread with its own session.
+    // Errors of a statement are sent to the client. Any other error closes the connection
+    // and is the result of its thread.
+    pub fn serve(&self) -> Result<()> {
+        for stream in self.listener.incoming() {
+            let stream = stream?;
+            let engine = self.engine.clone();
+            thread::spawn(move || Self::handle_connection(engine, stream));
+        }
+        Ok(())
+    }
+
+    fn handle_connection(engine: E, stream: TcpStream) -> Result<()> {
+        let mut session = engine.session()?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut writer = BufWriter::new(stream);
+
+        while let Some(request) = read_frame(&mut reader)? {
+            let response = String::from_utf8(request)
+                .map_err(|err| Error::Parse(err.to_string()))
+                .and_then(|sql| session.execute(&sql));
+            write_frame(&mut writer, &bincode::serialize(&response)?)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct Client {
+    reader: BufReader<TcpStream>,
+    writer: BufWriter<TcpStream>,
+}
+
+impl Client {
+    pub fn connect(addr: impl ToSocketAddrs) -> Result<Self> {
+        let stream = TcpStream::connect(addr)?;
+        Ok(Self {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: BufWriter::new(stream),
+        })
+    }
+
+    pub fn execute(&mut self, sql: &str) -> Result<ResultSet> {
+        write_frame(&mut self.writer, sql.as_bytes())?;
+        let response = read_frame(&mut self.reader)?
+            .ok_or(Error::Internal("[Client] connection closed".to_string()))?;
+        bincode::deserialize::<Result<ResultSet>>(&response)?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Cursor, thread};
+
+    use super::{read_frame, Client, Server, MAX_FRAME_SIZE};
+    use crate::{
+        error::{Error, Result},
+        sql::{engine::KvEngine, executor::ResultSet, types::Value},
+        storage::memory::MemoryEngine,
+    };
+
+    #[test]
+    fn test_server_roundtrip() -> Result<()> {
+        let server = Server::bind(KvEngine::new(MemoryEngine::new()), "127.0.0.1:0")?;
+        let addr = server.local_addr()?;
+        thread::spawn(move || server.serve());
+
+        let mut client = Client::connect(addr)?;
+        client.execute("create table t1 (a int primary key, b text);")?;
+        client.execute("insert into t1 values (1, 'a'), (2, 'b');")?;
+        assert!(client.execute("select * from t2;").is_err());
+
+        match client.execute("select * from t1;")? {
+            ResultSet::Scan { columns, rows, .. } => {
+                assert_eq!(columns, vec!["a".to_string(), "b".to_string()]);
+                assert_eq!(
+                    rows,
+                    vec![
+                        vec![Value::Integer(1), Value::String("a".to_string())],
+                        vec![Value::Integer(2), Value::String("b".to_string())],
+                    ]
+                );
+            }
+            r => panic!("unexpected result {:?}", r),
+        }
+        Ok(())
+    }
+    #[test]
+    fn test_server_non_finite_floats() -> Result<()> {
+        let server = Server::bind(KvEngine::new(MemoryEngine::new()), "127.0.0.1:0")?;
+        let addr = server.local_addr()?;
+        thread::spawn(move || server.serve());
+
+        let mut client = Client::connect(addr)?;
+        match client.execute("select float 'NaN', float 'inf', float '-inf';")? {
+            ResultSet::Scan { rows, .. } => {
+                assert!(matches!(rows[0][0], Value::Float(f) if f.is_nan()));
+                assert_eq!(
+                    rows[0][1..],
+                    [Value::Float(f64::INFINITY), Value::Float(f64::NEG_INFINITY)]
+                );
+            }
+            r => panic!("unexpected result {:?}", r),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_frame_too_large() -> Result<()> {
+        // Only the length is sent, the frame must be rejected before reading it.
+        let len = (MAX_FRAME_SIZE as u32 + 1).to_be_bytes();
+        assert_eq!(
+            read_frame(&mut Cursor::new(len)),
+            Err(Error::Internal(format!(
+                "frame of {} bytes exceeds the maximum of {} bytes",
+                MAX_FRAME_SIZE + 1,
+                MAX_FRAME_SIZE
+            )))
+        );
+
+        let mut frame = 3u32.to_be_bytes().to_vec();
+        frame.extend(b"abc");
+        assert_eq!(read_frame(&mut Cursor::new(frame))?, Some(b"abc".to_vec()));
+        Ok(())
+    }
+}
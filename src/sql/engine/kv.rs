@@ -5,91 +5,310 @@ use crate::{
     error::{Error, Result},
     sql::{
         schema::Table,
-        types::{Row, Value},
+        types::{Row, RowExt, Value},
     },
     storage::{
         engine::Engine as StorageEngine,
+        memory::MemoryEngine,
         mvcc::{Mvcc, MvccTransaction},
     },
 };
 
+const DEFAULT_MAX_ROW_SIZE: usize = 64 * 1024 * 1024;
+
 pub struct KvEngine<E: StorageEngine> {
     pub kv: Mvcc<E>,
+    // Prepended to every key, so that engines with different namespaces can share one
+    // storage engine without seeing each other's tables. A namespace must not be a
+    // prefix of another namespace on the same storage engine.
+    namespace: Vec<u8>,
+    change_log: bool,
+    max_row_size: usize,
 }
 
 impl<E: StorageEngine> Clone for KvEngine<E> {
     fn clone(&self) -> Self {
         KvEngine {
             kv: self.kv.clone(),
+            namespace: self.namespace.clone(),
+            change_log: self.change_log,
+            max_row_size: self.max_row_size,
         }
     }
 }
 
 impl<E: StorageEngine> KvEngine<E> {
     pub fn new(engine: E) -> Self {
+        Self::new_with_namespace(Mvcc::new(engine), Vec::new())
+    }
+
+    // Uses the keys under the namespace of a possibly shared storage engine.
+    pub fn new_with_namespace(kv: Mvcc<E>, namespace: Vec<u8>) -> Self {
         Self {
-            kv: Mvcc::new(engine),
+            kv,
+            namespace,
+            change_log: false,
+            max_row_size: DEFAULT_MAX_ROW_SIZE,
+        }
+    }
+
+    // Records every row mutation in an append-only change log, see changes_since.
+    pub fn with_change_log(mut self) -> Self {
+        self.change_log = true;
+        self
+    }
+
+    // Largest serialized row accepted by create_row, in bytes.
+    pub fn with_max_row_size(mut self, size: usize) -> Self {
+        self.max_row_size = size;
+        self
+    }
+
+    // Returns the logged changes with an lsn greater than the given one, in lsn order.
+    pub fn changes_since(&self, lsn: u64) -> Result<Vec<Change>> {
+        let txn = self.kv.begin()?;
+        let mut changes = Vec::new();
+        for result in txn.scan_prefix(encode_key(&self.namespace, &KeyPrefix::Change)?)? {
+            let change: Change = bincode::deserialize(&result.value)?;
+            if change.lsn > lsn {
+                changes.push(change);
+            }
+        }
+        changes.sort_by_key(|c| c.lsn);
+        Ok(changes)
+    }
+
+    // Copies every key/value pair into a fresh in-memory engine, giving a point-in-time
+    // copy of the database that can be changed without affecting this one.
+    pub fn snapshot(&self) -> Result<KvEngine<MemoryEngine>> {
+        let snapshot = Mvcc::new(MemoryEngine::new());
+        let from = KVTransaction::new(
+            self.kv.begin()?,
+            self.namespace.clone(),
+            self.change_log,
+            self.max_row_size,
+        );
+        let to = snapshot.begin()?;
+        for result in from.txn.scan_prefix(Vec::new())? {
+            to.set(result.key, result.value)?;
+        }
+        let lsn_key = encode_key(&self.namespace, &Key::LastLsn)?;
+        to.set_sequence(lsn_key.clone(), from.txn.sequence(lsn_key)?)?;
+        // The stored counts may include transactions committed after from began.
+        for table in from.scan_tables()? {
+            let prefix = from.encode(&KeyPrefix::Row(table.name.clone()))?;
+            let count = from.txn.scan_prefix(prefix)?.len() as u64;
+            to.set_sequence(from.encode(&Key::RowCount(table.name))?, count)?;
+        }
+        to.commit()?;
+        Ok(KvEngine {
+            kv: snapshot,
+            namespace: self.namespace.clone(),
+            change_log: self.change_log,
+            max_row_size: self.max_row_size,
+        })
+    }
+}
+
+impl KvEngine<MemoryEngine> {
+    // Creates an in-memory engine holding the given tables and rows, e.g. as test fixtures.
+    // Rows are checked against their table's schema like inserted rows are.
+    pub fn from_fixtures(tables: Vec<(Table, Vec<Row>)>) -> Result<Self> {
+        let engine = Self::new(MemoryEngine::new());
+        let mut txn = engine.begin()?;
+        for (table, rows) in tables {
+            if rows.iter().any(|row| row.len() != table.columns.len()) {
+                return Err(Error::Internal("row/schema arity mismatch".to_string()));
+            }
+            let table_name = table.name.clone();
+            let rows = rows
+                .into_iter()
+                .map(|row| table.coerce_row(row))
+                .collect::<Vec<_>>();
+            txn.create_table(table)?;
+            for row in rows {
+                txn.create_row(table_name.clone(), row)?;
+            }
         }
+        txn.commit()?;
+        Ok(engine)
     }
 }
 
-impl<E: StorageEngine> Engine for KvEngine<E> {
+impl<E: StorageEngine + 'static> Engine for KvEngine<E> {
     type Transaction = KVTransaction<E>;
 
     fn begin(&self) -> Result<Self::Transaction> {
-        Ok(Self::Transaction::new(self.kv.begin()?))
+        Ok(Self::Transaction::new(
+            self.kv.begin()?,
+            self.namespace.clone(),
+            self.change_log,
+            self.max_row_size,
+        ))
+    }
+
+    fn schema_version(&self) -> Result<u64> {
+        self.kv
+            .sequence(encode_key(&self.namespace, &Key::SchemaVersion)?)
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ChangeOp {
+    Insert,
+    Delete,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Change {
+    pub lsn: u64,
+    pub table_name: String,
+    pub op: ChangeOp,
+    pub key: Value,
+    pub row: Option<Row>,
+}
+
 pub struct KVTransaction<E: StorageEngine> {
     txn: MvccTransaction<E>,
+    namespace: Vec<u8>,
+    change_log: bool,
+    max_row_size: usize,
 }
 
 impl<E: StorageEngine> KVTransaction<E> {
-    pub fn new(txn: MvccTransaction<E>) -> Self {
-        Self { txn }
+    pub fn new(
+        txn: MvccTransaction<E>,
+        namespace: Vec<u8>,
+        change_log: bool,
+        max_row_size: usize,
+    ) -> Self {
+        Self {
+            txn,
+            namespace,
+            change_log,
+            max_row_size,
+        }
+    }
+
+    fn encode<K: Serialize>(&self, key: &K) -> Result<Vec<u8>> {
+        encode_key(&self.namespace, key)
+    }
+
+    fn log_change(
+        &self,
+        table_name: String,
+        op: ChangeOp,
+        key: Value,
+        row: Option<Row>,
+    ) -> Result<()> {
+        if !self.change_log {
+            return Ok(());
+        }
+
+        // The lsn counter is not versioned, so concurrent writers never conflict on it.
+        let lsn = self.txn.next_sequence(self.encode(&Key::LastLsn)?)?;
+        let change = Change {
+            lsn,
+            table_name,
+            op,
+            key,
+            row,
+        };
+        self.txn.set(
+            self.encode(&Key::Change(lsn))?,
+            bincode::serialize(&change)?,
+        )
+    }
+
+    fn adjust_row_count(&self, table_name: &str, delta: i64) -> Result<()> {
+        let key = self.encode(&Key::RowCount(table_name.to_string()))?;
+        self.txn.adjust_counter(key, delta);
+        Ok(())
     }
 }
 
 impl<E: StorageEngine> Transaction for KVTransaction<E> {
+    fn version(&self) -> u64 {
+        self.txn.version()
+    }
+
     fn commit(&self) -> Result<()> {
-        Ok(())
+        self.txn.commit()
     }
 
     fn rollback(&self) -> Result<()> {
-        Ok(())
+        self.txn.rollback()
     }
 
     fn create_row(&mut self, table_name: String, row: Row) -> Result<()> {
         let table = self.must_get_table(table_name.clone())?;
-        for (i, col) in table.columns.iter().enumerate() {
-            match row[i].datatype() {
-                None if col.nullable => {}
-                None => {
-                    return Err(Error::Internal(format!(
-                        "column {} cannot be null",
-                        col.name
-                    )))
-                }
-                Some(dt) if dt != col.datatype => {
-                    return Err(Error::Internal(format!(
-                        "column {} type mismatch",
-                        col.name
-                    )))
-                }
-                _ => {}
-            }
+        table.validate_row(&row)?;
+        let pk = table.get_row_key(&row)?;
+        if pk == Value::Null {
+            return Err(Error::Internal(format!(
+                "primary key {} cannot be null",
+                table.columns[table.primary_key_index()?].name
+            )));
         }
 
-        let id = Key::Row(table_name.clone(), row[0].clone());
+        let id = self.encode(&Key::Row(table_name.clone(), pk.clone()))?;
+        if self.txn.get(id.clone())?.is_some() {
+            return Err(Error::Internal(format!(
+                "primary key {} already exists in table {}",
+                pk, table_name
+            )));
+        }
         let value = bincode::serialize(&row)?;
-        self.txn.set(bincode::serialize(&id)?, value)?;
+        if value.len() > self.max_row_size {
+            return Err(Error::Internal("row exceeds max size".to_string()));
+        }
+        self.txn.set(id, value)?;
+        self.adjust_row_count(&table_name, 1)?;
+        self.log_change(table_name, ChangeOp::Insert, pk, Some(row))
+    }
+
+    fn delete_row(&mut self, table_name: String, id: Value) -> Result<()> {
+        let key = self.encode(&Key::Row(table_name.clone(), id.clone()))?;
+        if self.txn.get(key.clone())?.is_some() {
+            self.adjust_row_count(&table_name, -1)?;
+            self.log_change(table_name, ChangeOp::Delete, id, None)?;
+        }
+        self.txn.delete(key)
+    }
+
+    fn lock_row(&mut self, table_name: String, id: Value) -> Result<()> {
+        // Rewriting the row unchanged makes it a write of this transaction, which is
+        // what concurrent writers conflict with.
+        let key = self.encode(&Key::Row(table_name, id))?;
+        if let Some(value) = self.txn.get(key.clone())? {
+            self.txn.set(key, value)?;
+        }
+        Ok(())
+    }
+
+    fn load_rows_unchecked(&mut self, table_name: String, rows: Vec<Row>) -> Result<()> {
+        let table = self.must_get_table(table_name.clone())?;
+        for row in rows {
+            let pk = table.get_row_key(&row)?;
+            let id = self.encode(&Key::Row(table_name.clone(), pk.clone()))?;
+            if self.txn.get(id.clone())?.is_none() {
+                self.adjust_row_count(&table_name, 1)?;
+            }
+            self.txn.set(id, bincode::serialize(&row)?)?;
+            self.log_change(table_name.clone(), ChangeOp::Insert, pk, Some(row))?;
+        }
         Ok(())
     }
 
+    // Reads the count kept by create_row and delete_row. It is a counter applied on
+    // commit rather than a versioned key, so that concurrent inserts don't conflict.
+    fn row_count(&self, table_name: String) -> Result<u64> {
+        self.txn.counter(self.encode(&Key::RowCount(table_name))?)
+    }
+
     fn scan_table(&self, table_name: String) -> Result<Vec<Row>> {
         let prefix = KeyPrefix::Row(table_name.clone());
-        let results = self.txn.scan_prefix(bincode::serialize(&prefix)?)?;
+        let results = self.txn.scan_prefix(self.encode(&prefix)?)?;
         let mut rows = Vec::new();
         for result in results {
             let row: Row = bincode::deserialize(&result.value)?;
@@ -98,24 +317,61 @@ impl<E: StorageEngine> Transaction for KVTransaction<E> {
         Ok(rows)
     }
 
+    fn scan_table_lenient(&self, table_name: String) -> Result<(Vec<Row>, Vec<Value>)> {
+        let prefix = KeyPrefix::Row(table_name.clone());
+        let results = self.txn.scan_prefix(self.encode(&prefix)?)?;
+        let (mut rows, mut skipped) = (Vec::new(), Vec::new());
+        for result in results {
+            match bincode::deserialize(&result.value) {
+                Ok(row) => rows.push(row),
+                Err(_) => match bincode::deserialize(&result.key[self.namespace.len()..])? {
+                    Key::Row(_, id) => skipped.push(id),
+                    key => return Err(Error::Internal(format!("unexpected key {:?}", key))),
+                },
+            }
+        }
+        Ok((rows, skipped))
+    }
+
+    fn scan_table_where(
+        &self,
+        table_name: String,
+        col_index: usize,
+        value: &Value,
+    ) -> Result<Vec<Row>> {
+        let prefix = KeyPrefix::Row(table_name.clone());
+        let results = self.txn.scan_prefix(self.encode(&prefix)?)?;
+        let mut rows = Vec::new();
+        if *value == Value::Null {
+            return Ok(rows);
+        }
+        for result in results {
+            let row: Row = bincode::deserialize(&result.value)?;
+            let matches = match row.value(col_index)? {
+                Value::Null => false,
+                v => v.compare(value)?.is_eq(),
+            };
+            if matches {
+                rows.push(row);
+            }
+        }
+        Ok(rows)
+    }
+
     fn create_table(&self, table: Table) -> Result<()> {
-        if self.get_table(table.name.clone())?.is_some() {
+        if self.table_exists(table.name.clone())? {
             return Err(Error::Internal(format!(
                 "table {} already exists",
                 table.name
             )));
         }
 
-        if table.columns.is_empty() {
-            return Err(Error::Internal(format!(
-                "table {} has no columns",
-                table.name
-            )));
-        }
+        table.validate()?;
 
         let key = Key::Table(table.name.clone());
         let value = bincode::serialize(&table)?;
-        self.txn.set(bincode::serialize(&key)?, value)?;
+        self.txn.set(self.encode(&key)?, value)?;
+        self.txn.next_sequence(self.encode(&Key::SchemaVersion)?)?;
         Ok(())
     }
 
@@ -123,36 +379,84 @@ impl<E: StorageEngine> Transaction for KVTransaction<E> {
         let key = Key::Table(table_name);
         let v = self
             .txn
-            .get(bincode::serialize(&key)?)?
+            .get(self.encode(&key)?)?
             .map(|v| bincode::deserialize(&v))
             .transpose()?;
         Ok(v)
     }
+
+    fn scan_tables(&self) -> Result<Vec<Table>> {
+        let prefix = KeyPrefix::Table;
+        let results = self.txn.scan_prefix(self.encode(&prefix)?)?;
+        let mut tables = Vec::new();
+        for result in results {
+            tables.push(bincode::deserialize(&result.value)?);
+        }
+        Ok(tables)
+    }
+
+    fn table_exists(&self, table_name: String) -> Result<bool> {
+        let key = Key::Table(table_name);
+        Ok(self.txn.get(self.encode(&key)?)?.is_some())
+    }
+}
+
+fn encode_key<K: Serialize>(namespace: &[u8], key: &K) -> Result<Vec<u8>> {
+    let mut encoded = namespace.to_vec();
+    encoded.extend(bincode::serialize(key)?);
+    Ok(encoded)
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 enum Key {
     Table(String),
     Row(String, Value),
+    Change(u64),
+    LastLsn,
+    SchemaVersion,
+    RowCount(String),
 }
 
+// Variants must line up with Key so that the serialized prefix matches.
 #[derive(Debug, Serialize, Deserialize)]
 enum KeyPrefix {
     Table,
     Row(String),
+    Change,
 }
 
 #[cfg(test)]
 mod tests {
-    use super::KvEngine;
-    use crate::{error::Result, sql::engine::Engine, storage::memory::MemoryEngine};
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::{encode_key, Change, ChangeOp, Key, KvEngine};
+    use crate::{
+        error::{Error, Result},
+        sql::{
+            engine::{Engine, PlanCache, Session, Transaction},
+            executor::{ExecContext, ResultSet},
+            parser::{is_statement_complete, normalize, Keyword, Parser},
+            plan::{Plan, Planner},
+            schema::{Column, Table},
+            types::{DataType, IntegerDivision, Row, Value},
+        },
+        storage::{memory::MemoryEngine, mvcc::Mvcc},
+    };
+
+    // Returns the rows selected by the query.
+    fn select(s: &mut Session<KvEngine<MemoryEngine>>, sql: &str) -> Result<Vec<Row>> {
+        match s.execute(sql)? {
+            ResultSet::Scan { rows, .. } => Ok(rows),
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
 
     #[test]
     fn test_create_table() -> Result<()> {
         let kv_engine = KvEngine::new(MemoryEngine::new());
         let mut s = kv_engine.session()?;
 
-        s.execute("create table t1 (a int, b text, c integer);")?;
+        s.execute("create table t1 (a int primary key, b text, c integer);")?;
 
         s.execute("insert into t1 values(1, 'a', 1);")?;
 
@@ -160,4 +464,1965 @@ mod tests {
         print!("{:?}", v1);
         Ok(())
     }
+
+    #[test]
+    fn test_table_exists() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let txn = kv_engine.begin()?;
+        assert!(!txn.table_exists("t1".to_string())?);
+
+        let mut s = kv_engine.session()?;
+        s.execute("create table t1 (a int primary key);")?;
+        // The table was created after txn began, so only later transactions see it.
+        assert!(!txn.table_exists("t1".to_string())?);
+        let txn = kv_engine.begin()?;
+        assert!(txn.table_exists("t1".to_string())?);
+        assert!(!txn.table_exists("t2".to_string())?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_replace_into() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+
+        s.execute("create table t1 (a int primary key, b text);")?;
+        s.execute("insert into t1 values (1, 'a'), (2, 'b');")?;
+        s.execute("replace into t1 values (1, 'c');")?;
+
+        match s.execute("select * from t1;")? {
+            ResultSet::Scan { rows, .. } => assert_eq!(
+                rows,
+                vec![
+                    vec![Value::Integer(1), Value::String("c".to_string())],
+                    vec![Value::Integer(2), Value::String("b".to_string())],
+                ]
+            ),
+            r => panic!("unexpected result {:?}", r),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_column_comment() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        s.execute("create table t1 (a int primary key comment 'the id', b text not null);")?;
+        s.execute("insert into t1 values (1, 'a');")?;
+
+        let table = kv_engine.begin()?.must_get_table("t1".to_string())?;
+        assert_eq!(table.columns[0].comment, Some("the id".to_string()));
+        assert_eq!(table.columns[1].comment, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_null_primary_key() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        s.execute("create table t1 (a int primary key, b text);")?;
+
+        assert!(s.execute("insert into t1 values (null, 'a');").is_err());
+        assert!(s.execute("insert into t1 (b) values ('a');").is_err());
+        match s.execute("select * from t1;")? {
+            ResultSet::Scan { rows, .. } => assert!(rows.is_empty()),
+            r => panic!("unexpected result {:?}", r),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_insert_rollback() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        s.execute("create table t1 (a int primary key, b text);")?;
+
+        // The second row fails type validation after the first one was written.
+        assert!(s
+            .execute("insert into t1 values (1, 'a'), (2, 3);")
+            .is_err());
+        match s.execute("select * from t1;")? {
+            ResultSet::Scan { rows, .. } => assert!(rows.is_empty()),
+            r => panic!("unexpected result {:?}", r),
+        }
+        assert_eq!(kv_engine.begin()?.row_count("t1".to_string())?, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_collect_insert_errors() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        s.execute("create table t1 (a int primary key, b text not null, c float);")?;
+        s.execute("insert into t1 values (1, 'a', 1.0);")?;
+
+        s.set_collect_insert_errors(true);
+        let sql =
+            "replace into t1 values (1, null, 1.5), (2, 'b', 2.0), (3, 'c', 'x'), (4, 'd', 4.0);";
+        match s.execute(sql)? {
+            ResultSet::Insert { count, errors } => {
+                assert_eq!(count, 2);
+                assert_eq!(
+                    errors,
+                    vec![
+                        (0, Error::Internal("column b cannot be null".to_string())),
+                        (2, Error::Internal("column c type mismatch".to_string())),
+                    ]
+                );
+            }
+            r => panic!("unexpected result {:?}", r),
+        }
+        match s.execute("select a, b from t1;")? {
+            ResultSet::Scan { rows, .. } => assert_eq!(
+                rows,
+                vec![
+                    vec![Value::Integer(1), Value::String("a".to_string())],
+                    vec![Value::Integer(2), Value::String("b".to_string())],
+                    vec![Value::Integer(4), Value::String("d".to_string())],
+                ]
+            ),
+            r => panic!("unexpected result {:?}", r),
+        }
+
+        s.set_collect_insert_errors(false);
+        let sql = "insert into t1 values (5, 'e', 5.0), (6, null, 6.0);";
+        assert!(s.execute(sql).is_err());
+        assert_eq!(kv_engine.begin()?.row_count("t1".to_string())?, 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_primary_key() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        s.execute("create table t1 (a text, b int primary key);")?;
+        s.execute("insert into t1 values ('x', 1), ('y', 2);")?;
+        s.execute("update t1 set b = 3 where b = 2;")?;
+        s.execute("delete from t1 where a = 'x';")?;
+        s.execute("insert into t1 values ('z', 1);")?;
+
+        let table = kv_engine.begin()?.must_get_table("t1".to_string())?;
+        assert_eq!(table.primary_key_index()?, 1);
+        assert!(!table.columns[1].nullable);
+        match s.execute("select * from t1 order by b;")? {
+            ResultSet::Scan { rows, .. } => assert_eq!(
+                rows,
+                vec![
+                    vec![Value::String("z".to_string()), Value::Integer(1)],
+                    vec![Value::String("y".to_string()), Value::Integer(3)],
+                ]
+            ),
+            r => panic!("unexpected result {:?}", r),
+        }
+        assert_eq!(kv_engine.begin()?.row_count("t1".to_string())?, 2);
+        assert!(s.execute("insert into t1 values ('w', null);").is_err());
+
+        assert_eq!(
+            s.execute("create table t2 (a int, b int);").unwrap_err(),
+            Error::Internal("table t2 has no primary key".to_string())
+        );
+        assert_eq!(
+            s.execute("create table t2 (a int primary key, b int primary key);")
+                .unwrap_err(),
+            Error::Internal("table t2 has multiple primary keys".to_string())
+        );
+        assert!(s
+            .execute("create table t2 (a int primary key null);")
+            .is_err());
+        assert!(s.execute("create table t2 (a int primary);").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_duplicate_primary_key() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        s.execute("create table t1 (a int primary key, b text);")?;
+        s.execute("insert into t1 values (1, 'a'), (2, 'b');")?;
+
+        assert_eq!(
+            s.execute("insert into t1 values (1, 'c');").unwrap_err(),
+            Error::Internal("primary key 1 already exists in table t1".to_string())
+        );
+        assert!(s
+            .execute("insert into t1 values (3, 'c'), (3, 'd');")
+            .is_err());
+        assert!(s.execute("update t1 set a = 2 where a = 1;").is_err());
+        s.execute("update t1 set b = 'c' where a = 1;")?;
+        s.execute("replace into t1 values (2, 'd');")?;
+
+        match s.execute("select * from t1;")? {
+            ResultSet::Scan { rows, .. } => assert_eq!(
+                rows,
+                vec![
+                    vec![Value::Integer(1), Value::String("c".to_string())],
+                    vec![Value::Integer(2), Value::String("d".to_string())],
+                ]
+            ),
+            r => panic!("unexpected result {:?}", r),
+        }
+        assert_eq!(kv_engine.begin()?.row_count("t1".to_string())?, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_row_size() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new()).with_max_row_size(64);
+        let mut s = kv_engine.session()?;
+        s.execute("create table t1 (a int primary key, b text);")?;
+        s.execute("insert into t1 values (1, 'short');")?;
+
+        let long = "x".repeat(64);
+        let sql = format!("insert into t1 values (2, '{}');", long);
+        assert_eq!(
+            s.execute(&sql).unwrap_err(),
+            Error::Internal("row exceeds max size".to_string())
+        );
+        let sql = format!("update t1 set b = '{}' where a = 1;", long);
+        assert!(s.execute(&sql).is_err());
+        assert_eq!(kv_engine.begin()?.row_count("t1".to_string())?, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_insert_default_values() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        s.execute("create table t1 (a int primary key default 1, b text default 'x', c float);")?;
+        s.execute("insert into t1 default values;")?;
+
+        match s.execute("select * from t1;")? {
+            ResultSet::Scan { rows, .. } => assert_eq!(
+                rows,
+                vec![vec![
+                    Value::Integer(1),
+                    Value::String("x".to_string()),
+                    Value::Null
+                ]]
+            ),
+            r => panic!("unexpected result {:?}", r),
+        }
+        assert!(s.execute("insert into t1 default values;").is_err());
+        s.execute("replace into t1 default values;")?;
+        assert!(s.execute("insert into t1 default;").is_err());
+        assert!(s.execute("insert into t1 default values (1);").is_err());
+
+        s.execute("create table t2 (a int primary key, b text default 'x');")?;
+        assert_eq!(
+            s.execute("insert into t2 default values;").unwrap_err(),
+            Error::Internal("No default Value for column a".to_string())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_rows_unchecked() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        s.execute("create table t1 (a int primary key, b text);")?;
+
+        let rows = (0..100)
+            .map(|i| vec![Value::Integer(i), Value::String(i.to_string())])
+            .collect::<Vec<_>>();
+        let mut txn = kv_engine.begin()?;
+        txn.load_rows_unchecked("t1".to_string(), rows.clone())?;
+        txn.commit()?;
+
+        s.execute("insert into t1 values (100, '100');")?;
+        match s.execute("select * from t1;")? {
+            ResultSet::Scan { rows: scanned, .. } => {
+                assert_eq!(scanned.len(), 101);
+                assert!(rows.iter().all(|row| scanned.contains(row)));
+            }
+            r => panic!("unexpected result {:?}", r),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_arity_mismatch() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        s.execute("create table t1 (a int primary key, b text);")?;
+
+        let mut txn = kv_engine.begin()?;
+        txn.load_rows_unchecked("t1".to_string(), vec![vec![Value::Integer(1)]])?;
+        txn.commit()?;
+
+        assert_eq!(
+            s.execute("select * from t1;").unwrap_err(),
+            Error::Internal("row/schema arity mismatch".to_string())
+        );
+
+        // Rows with too many values are rejected on write, so the table stays readable.
+        s.execute("create table t2 (a int primary key, b text);")?;
+        assert_eq!(
+            s.execute("insert into t2 values (1, 'a', 'extra');")
+                .unwrap_err(),
+            Error::Internal("row/schema arity mismatch".to_string())
+        );
+        s.execute("insert into t2 values (2, 'b');")?;
+        assert_eq!(
+            select(&mut s, "select * from t2;")?,
+            vec![vec![Value::Integer(2), Value::String("b".to_string())]]
+        );
+        let mut txn = kv_engine.begin()?;
+        assert!(txn
+            .create_row("t2".to_string(), vec![Value::Integer(3)])
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_lenient_scan() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        s.execute("create table t1 (a int primary key, b text);")?;
+        s.execute("insert into t1 values (1, 'a'), (3, 'c');")?;
+
+        // Stores a row which doesn't deserialize.
+        let txn = kv_engine.kv.begin()?;
+        let key = Key::Row("t1".to_string(), Value::Integer(2));
+        txn.set(encode_key(&[], &key)?, vec![0xff, 0xff])?;
+        txn.commit()?;
+
+        assert!(s.execute("select * from t1;").is_err());
+
+        s.set_lenient_scan(true);
+        let scan = |result: Result<ResultSet>| match result {
+            Ok(ResultSet::Scan { rows, skipped, .. }) => (rows, skipped),
+            r => panic!("unexpected result {:?}", r),
+        };
+        assert_eq!(
+            scan(s.execute("select * from t1;")),
+            (
+                vec![
+                    vec![Value::Integer(1), Value::String("a".to_string())],
+                    vec![Value::Integer(3), Value::String("c".to_string())],
+                ],
+                vec![Value::Integer(2)]
+            )
+        );
+        assert_eq!(
+            scan(s.execute("select a from t1 where a = 3;")),
+            (vec![vec![Value::Integer(3)]], vec![Value::Integer(2)])
+        );
+        s.execute("begin;")?;
+        assert_eq!(
+            scan(s.execute("select count(*) from t1;")),
+            (vec![vec![Value::Integer(2)]], vec![Value::Integer(2)])
+        );
+        s.execute("commit;")?;
+        s.set_lenient_scan(false);
+        assert!(s.execute("select * from t1;").is_err());
+
+        // Writes still fail on the unreadable row.
+        assert!(s.execute("delete from t1;").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_row_count() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        s.execute("create table t1 (a int primary key, b text);")?;
+        assert_eq!(kv_engine.begin()?.row_count("t1".to_string())?, 0);
+
+        s.execute("insert into t1 values (1, 'a'), (2, 'b'), (3, 'c');")?;
+        s.execute("replace into t1 values (2, 'd');")?;
+        assert_eq!(kv_engine.begin()?.row_count("t1".to_string())?, 3);
+
+        let mut txn = kv_engine.begin()?;
+        txn.delete_row("t1".to_string(), Value::Integer(1))?;
+        txn.delete_row("t1".to_string(), Value::Integer(4))?;
+        txn.commit()?;
+        assert_eq!(kv_engine.begin()?.row_count("t1".to_string())?, 2);
+
+        // Rolled back inserts and deletes leave the count unchanged.
+        s.execute("begin;")?;
+        s.execute("insert into t1 values (5, 'e');")?;
+        s.execute("delete from t1 where a = 2;")?;
+        s.execute("rollback;")?;
+        assert_eq!(kv_engine.begin()?.row_count("t1".to_string())?, 2);
+
+        let mut txn = kv_engine.begin()?;
+        txn.create_row("t1".to_string(), vec![Value::Integer(6), Value::Null])?;
+        assert_eq!(txn.row_count("t1".to_string())?, 3);
+        txn.rollback()?;
+        assert_eq!(kv_engine.begin()?.row_count("t1".to_string())?, 2);
+        assert_eq!(
+            kv_engine.snapshot()?.begin()?.row_count("t1".to_string())?,
+            2
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_bytes_value() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        s.execute("create table t1 (a int primary key, b blob);")?;
+        s.execute("insert into t1 values (1, x'00FF'), (2, null);")?;
+        assert!(s.execute("insert into t1 values (3, '00FF');").is_err());
+
+        match s.execute("select * from t1;")? {
+            ResultSet::Scan { rows, .. } => assert_eq!(
+                rows,
+                vec![
+                    vec![Value::Integer(1), Value::Bytes(vec![0x00, 0xff])],
+                    vec![Value::Integer(2), Value::Null],
+                ]
+            ),
+            r => panic!("unexpected result {:?}", r),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_change_log() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new()).with_change_log();
+        let mut s = kv_engine.session()?;
+        s.execute("create table t1 (a int primary key, b text);")?;
+        s.execute("insert into t1 values (1, 'a'), (2, 'b');")?;
+        s.execute("replace into t1 values (1, 'c');")?;
+        let mut txn = kv_engine.begin()?;
+        txn.delete_row("t1".to_string(), Value::Integer(2))?;
+        txn.commit()?;
+
+        let change = |lsn, op, key, row: Option<Vec<Value>>| Change {
+            lsn,
+            table_name: "t1".to_string(),
+            op,
+            key: Value::Integer(key),
+            row,
+        };
+        let row = |a, b: &str| Some(vec![Value::Integer(a), Value::String(b.to_string())]);
+        assert_eq!(
+            kv_engine.changes_since(0)?,
+            vec![
+                change(1, ChangeOp::Insert, 1, row(1, "a")),
+                change(2, ChangeOp::Insert, 2, row(2, "b")),
+                change(3, ChangeOp::Delete, 1, None),
+                change(4, ChangeOp::Insert, 1, row(1, "c")),
+                change(5, ChangeOp::Delete, 2, None),
+            ]
+        );
+        assert_eq!(kv_engine.changes_since(4)?.len(), 1);
+
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        s.execute("create table t1 (a int primary key);")?;
+        s.execute("insert into t1 values (1);")?;
+        assert!(kv_engine.changes_since(0)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_statement_logger() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        let logged = Rc::new(RefCell::new(Vec::new()));
+        let sink = logged.clone();
+        s.set_statement_logger(Box::new(move |sql, result| {
+            sink.borrow_mut().push((sql.to_string(), result.is_ok()));
+        }));
+
+        s.execute("create table t1 (a int primary key);")?;
+        assert!(s.execute("select * from t2;").is_err());
+        assert_eq!(
+            *logged.borrow(),
+            vec![
+                ("create table t1 (a int primary key);".to_string(), true),
+                ("select * from t2;".to_string(), false),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_transaction_statements() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        let mut other = kv_engine.session()?;
+        s.execute("create table t1 (a int primary key);")?;
+        fn count(s: &mut Session<KvEngine<MemoryEngine>>) -> Result<usize> {
+            match s.execute("select * from t1;")? {
+                ResultSet::Scan { rows, .. } => Ok(rows.len()),
+                r => panic!("unexpected result {:?}", r),
+            }
+        }
+
+        // Statements between BEGIN and COMMIT only become visible to others on commit.
+        let version = match s.execute("begin;")? {
+            ResultSet::Begin { version } => version,
+            r => panic!("unexpected result {:?}", r),
+        };
+        s.execute("insert into t1 values (1);")?;
+        s.execute("insert into t1 values (2);")?;
+        assert_eq!(count(&mut s)?, 2);
+        assert_eq!(count(&mut other)?, 0);
+        assert!(s.execute("begin;").is_err());
+        assert!(matches!(
+            s.execute("commit;")?,
+            ResultSet::Commit { version: v } if v == version
+        ));
+        assert_eq!(count(&mut other)?, 2);
+
+        s.execute("begin;")?;
+        s.execute("delete from t1 where a = 1;")?;
+        assert!(matches!(
+            s.execute("rollback;")?,
+            ResultSet::Rollback { .. }
+        ));
+        assert_eq!(count(&mut s)?, 2);
+
+        // A failed statement aborts the transaction, discarding its earlier statements.
+        // Later statements fail instead of running on their own until it is ended.
+        let version = match s.execute("begin;")? {
+            ResultSet::Begin { version } => version,
+            r => panic!("unexpected result {:?}", r),
+        };
+        s.execute("insert into t1 values (3);")?;
+        assert!(s.execute("insert into t1 values (1);").is_err());
+        let aborted = Error::Internal(
+            "transaction aborted, statements are ignored until COMMIT or ROLLBACK".to_string(),
+        );
+        assert_eq!(
+            s.execute("insert into t1 values (4);").unwrap_err(),
+            aborted
+        );
+        assert_eq!(s.execute("select * from t1;").unwrap_err(), aborted);
+        assert_eq!(s.execute("begin;").unwrap_err(), aborted);
+        assert_eq!(count(&mut other)?, 2);
+        assert!(matches!(
+            s.execute("commit;")?,
+            ResultSet::Rollback { version: v } if v == version
+        ));
+        assert_eq!(
+            s.execute("commit;").unwrap_err(),
+            Error::Internal("no active transaction".to_string())
+        );
+        assert_eq!(count(&mut s)?, 2);
+
+        s.execute("begin;")?;
+        assert!(s.execute("insert into t1 values (1);").is_err());
+        assert!(matches!(
+            s.execute("rollback;")?,
+            ResultSet::Rollback { .. }
+        ));
+        s.execute("insert into t1 values (4);")?;
+        assert_eq!(count(&mut s)?, 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_concurrent_transactions() -> Result<()> {
+        const THREADS: i64 = 4;
+        const ITERATIONS: i64 = 40;
+        const ACCOUNTS: i64 = 5;
+        type KvSession = Session<KvEngine<MemoryEngine>>;
+
+        let kv_engine = KvEngine::new(MemoryEngine::new()).with_change_log();
+        let mut s = kv_engine.session()?;
+        s.execute("create table accounts (id int primary key, balance int);")?;
+        s.execute("create table counter (id int primary key, n int);")?;
+        s.execute("create table events (id int primary key, thread int);")?;
+        s.execute("create table inserts (id int primary key, thread int);")?;
+        for id in 0..ACCOUNTS {
+            s.execute(&format!("insert into accounts values ({}, 100);", id))?;
+        }
+        s.execute("insert into counter values (0, 0);")?;
+
+        // Runs the statements in one transaction, retrying it on write conflicts.
+        fn run(s: &mut KvSession, stmts: &[String]) -> Result<()> {
+            loop {
+                s.execute("begin;")?;
+                let result = stmts
+                    .iter()
+                    .chain([&"commit;".to_string()])
+                    .try_for_each(|sql| s.execute(sql).map(|_| ()));
+                match result {
+                    Ok(()) => return Ok(()),
+                    // The failed statement already rolled the transaction back, which
+                    // only has to be ended.
+                    Err(Error::WriteConflict) => {
+                        s.execute("rollback;")?;
+                    }
+                    Err(err) => return Err(err),
+                }
+            }
+        }
+
+        // Moves money between accounts and counts the transfers, while every other
+        // transfer deletes the event row inserted by the one before.
+        let writer = |thread: i64| -> Result<()> {
+            let mut s = kv_engine.session()?;
+            for i in 0..ITERATIONS {
+                let (from, to) = ((thread + i) % ACCOUNTS, (thread + i + 1) % ACCOUNTS);
+                let id = thread * ITERATIONS + i;
+                let mut stmts = vec![
+                    format!(
+                        "update accounts set balance = balance - 7 where id = {};",
+                        from
+                    ),
+                    format!(
+                        "update accounts set balance = balance + 7 where id = {};",
+                        to
+                    ),
+                    "update counter set n = n + 1 where id = 0;".to_string(),
+                    format!("insert into events values ({}, {});", id, thread),
+                ];
+                if i % 2 == 1 {
+                    stmts.push(format!("delete from events where id = {};", id - 1));
+                }
+                run(&mut s, &stmts)?;
+            }
+            Ok(())
+        };
+        // Inserts of disjoint rows into the same table must never conflict, so they
+        // are not retried.
+        let inserter = |thread: i64| -> Result<()> {
+            let mut s = kv_engine.session()?;
+            for i in 0..ITERATIONS {
+                s.execute("begin;")?;
+                s.execute(&format!(
+                    "insert into inserts values ({}, {});",
+                    thread * ITERATIONS + i,
+                    thread
+                ))?;
+                s.execute("commit;")?;
+            }
+            Ok(())
+        };
+        // Every scan must see whole rows of a consistent snapshot.
+        let reader = || -> Result<()> {
+            let mut s = kv_engine.session()?;
+            for _ in 0..ITERATIONS {
+                let rows = match s.execute("select * from accounts;")? {
+                    ResultSet::Scan { rows, .. } => rows,
+                    r => panic!("unexpected result {:?}", r),
+                };
+                assert_eq!(rows.len() as i64, ACCOUNTS);
+                assert!(rows.iter().all(|row| row.len() == 2));
+                let total = rows
+                    .iter()
+                    .map(|row| row[1].as_i64())
+                    .sum::<Result<Option<i64>>>()?;
+                assert_eq!(total, Some(ACCOUNTS * 100));
+            }
+            Ok(())
+        };
+
+        std::thread::scope(|scope| {
+            let mut handles = (0..THREADS)
+                .map(|thread| scope.spawn(move || writer(thread)))
+                .collect::<Vec<_>>();
+            handles.extend((0..THREADS).map(|thread| scope.spawn(move || inserter(thread))));
+            handles.push(scope.spawn(reader));
+            handles
+                .into_iter()
+                .try_for_each(|handle| handle.join().expect("thread panicked"))
+        })?;
+
+        let txn = kv_engine.begin()?;
+        let balances = txn.scan_table("accounts".to_string())?;
+        let total: i64 = balances
+            .iter()
+            .map(|row| row[1].as_i64().unwrap().unwrap())
+            .sum();
+        assert_eq!(total, ACCOUNTS * 100);
+        assert_eq!(
+            txn.scan_table("counter".to_string())?,
+            vec![vec![
+                Value::Integer(0),
+                Value::Integer(THREADS * ITERATIONS)
+            ]]
+        );
+        let events = txn.scan_table("events".to_string())?;
+        assert_eq!(events.len() as i64, THREADS * ITERATIONS / 2);
+        assert_eq!(txn.row_count("events".to_string())?, events.len() as u64);
+        assert_eq!(
+            txn.row_count("inserts".to_string())?,
+            (THREADS * ITERATIONS) as u64
+        );
+
+        // Overlapping transactions inserting different rows both commit.
+        let (mut s1, mut s2) = (kv_engine.session()?, kv_engine.session()?);
+        s1.execute("begin;")?;
+        s2.execute("begin;")?;
+        s1.execute("insert into inserts values (-1, 1);")?;
+        s2.execute("insert into inserts values (-2, 2);")?;
+        s1.execute("commit;")?;
+        s2.execute("commit;")?;
+        assert_eq!(
+            kv_engine.begin()?.row_count("inserts".to_string())?,
+            (THREADS * ITERATIONS) as u64 + 2
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_temporary_table() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s1 = kv_engine.session()?;
+        s1.execute("create table t1 (a int primary key);")?;
+        s1.execute("insert into t1 values (1);")?;
+        s1.execute("create temp table t1 (a int primary key, b text);")?;
+        s1.execute("create temporary table t2 (a int primary key);")?;
+        s1.execute("insert into t1 values (2, 'b');")?;
+
+        match s1.execute("select * from t1;")? {
+            ResultSet::Scan { rows, .. } => assert_eq!(
+                rows,
+                vec![vec![Value::Integer(2), Value::String("b".to_string())]]
+            ),
+            r => panic!("unexpected result {:?}", r),
+        }
+        assert!(!kv_engine.begin()?.table_exists("t2".to_string())?);
+
+        let mut s2 = kv_engine.session()?;
+        assert!(s2.execute("select * from t2;").is_err());
+        match s2.execute("select * from t1;")? {
+            ResultSet::Scan { rows, .. } => assert_eq!(rows, vec![vec![Value::Integer(1)]]),
+            r => panic!("unexpected result {:?}", r),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_bigint_value() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        s.execute("create table t1 (a bigint primary key, b int);")?;
+        s.execute("insert into t1 values (9223372036854775808, 1), (2, 2);")?;
+        assert!(s
+            .execute("insert into t1 values (3, 9223372036854775808);")
+            .is_err());
+
+        match s.execute("select * from t1;")? {
+            ResultSet::Scan { rows, .. } => {
+                let mut values = rows
+                    .iter()
+                    .map(|row| row[0].as_i128())
+                    .collect::<Result<Vec<_>>>()?;
+                values.sort();
+                assert_eq!(values, vec![Some(2), Some(i64::MAX as i128 + 1)]);
+                assert!(rows.contains(&vec![Value::BigInt(2), Value::Integer(2)]));
+            }
+            r => panic!("unexpected result {:?}", r),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_export_schema() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        s.execute("create table t1 (a int primary key, b text default 'x');")?;
+        s.execute("create table t2 (c bool primary key comment 'flag');")?;
+        s.execute("insert into t1 values (1, 'a');")?;
+
+        let schema = s.export_schema()?;
+        assert_eq!(schema.as_array().map(|tables| tables.len()), Some(2));
+        assert_eq!(schema[0]["name"], "t1");
+        assert_eq!(schema[0]["columns"][0]["name"], "a");
+        assert_eq!(schema[0]["columns"][0]["datatype"], "Integer");
+        assert_eq!(schema[0]["columns"][0]["nullable"], false);
+        assert_eq!(schema[0]["columns"][0]["primary_key"], true);
+        assert_eq!(schema[0]["columns"][1]["primary_key"], false);
+        assert!(schema[0]["columns"][0]["default"].is_null());
+        assert_eq!(schema[0]["columns"][1]["default"]["String"], "x");
+        assert_eq!(schema[1]["name"], "t2");
+        assert_eq!(schema[1]["columns"][0]["datatype"], "Boolean");
+        assert_eq!(schema[1]["columns"][0]["comment"], "flag");
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_schema() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        s.execute("create table t1 (a int primary key, b text default 'x');")?;
+        s.execute("create table t2 (c bool primary key comment 'flag');")?;
+        let schema = s.export_schema()?;
+
+        let mut s2 = KvEngine::new(MemoryEngine::new()).session()?;
+        s2.import_schema(schema.clone())?;
+        assert_eq!(s2.export_schema()?, schema);
+        s2.execute("insert into t1 values (1, 'a');")?;
+
+        assert!(s2.import_schema(schema).is_err());
+        assert!(s2.import_schema(serde_json::Value::Bool(true)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_table() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        s.execute("create table t1 (a int primary key, b text not null);")?;
+        s.execute("insert into t1 values (1, 'a');")?;
+        s.verify_table("t1")?;
+        assert!(s.verify_table("t2").is_err());
+
+        let mut txn = kv_engine.begin()?;
+        txn.load_rows_unchecked("t1".to_string(), vec![vec![Value::Integer(2), Value::Null]])?;
+        txn.commit()?;
+        assert_eq!(
+            s.verify_table("t1"),
+            Err(Error::Internal(
+                "table t1 row [Integer(2), Null] is invalid: Internal(\"column b cannot be null\")"
+                    .to_string()
+            ))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        s.execute("create table t1 (a int primary key, b text);")?;
+        s.execute("insert into t1 values (1, 'a');")?;
+
+        let snapshot = kv_engine.snapshot()?;
+        s.execute("insert into t1 values (2, 'b');")?;
+        s.execute("create table t2 (a int primary key);")?;
+
+        let txn = snapshot.begin()?;
+        assert_eq!(
+            txn.scan_table("t1".to_string())?,
+            vec![vec![Value::Integer(1), Value::String("a".to_string())]]
+        );
+        assert_eq!(txn.row_count("t1".to_string())?, 1);
+        assert!(!txn.table_exists("t2".to_string())?);
+
+        snapshot
+            .session()?
+            .execute("insert into t1 values (3, 'c');")?;
+        assert_eq!(kv_engine.begin()?.row_count("t1".to_string())?, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_namespace() -> Result<()> {
+        let kv = Mvcc::new(MemoryEngine::new());
+        let engine_a = KvEngine::new_with_namespace(kv.clone(), b"a".to_vec());
+        let engine_b = KvEngine::new_with_namespace(kv.clone(), b"b".to_vec());
+        let mut s = engine_a.session()?;
+        s.execute("create table t1 (a int primary key);")?;
+        s.execute("insert into t1 values (1), (2);")?;
+
+        let mut s = engine_b.session()?;
+        assert!(!engine_b.begin()?.table_exists("t1".to_string())?);
+        s.execute("create table t1 (a int primary key, b text);")?;
+        s.execute("insert into t1 values (3, 'c');")?;
+
+        let (txn_a, txn_b) = (engine_a.begin()?, engine_b.begin()?);
+        assert_eq!(txn_a.scan_tables()?.len(), 1);
+        assert_eq!(txn_a.must_get_table("t1".to_string())?.columns.len(), 1);
+        assert_eq!(txn_a.row_count("t1".to_string())?, 2);
+        assert_eq!(
+            txn_b.scan_table("t1".to_string())?,
+            vec![vec![Value::Integer(3), Value::String("c".to_string())]]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_where() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        s.execute("create table t1 (a int primary key, b text, c bigint);")?;
+        s.execute("insert into t1 values (1, 'a', 10), (2, null, 20), (3, 'a', 30);")?;
+
+        let (v1, v2, v3) = (Value::Integer(1), Value::Integer(2), Value::Integer(3));
+        let sql = "select a from t1 where a = 2;";
+        assert_eq!(select(&mut s, sql)?, vec![vec![v2]]);
+        let sql = "select a from t1 where b = 'a';";
+        assert_eq!(
+            select(&mut s, sql)?,
+            vec![vec![v1.clone()], vec![v3.clone()]]
+        );
+        let sql = "select a from t1 where 'a' = b;";
+        assert_eq!(select(&mut s, sql)?, vec![vec![v1], vec![v3.clone()]]);
+        let sql = "select a from t1 where c = 30;";
+        assert_eq!(select(&mut s, sql)?, vec![vec![v3]]);
+        assert!(select(&mut s, "select * from t1 where b = null;")?.is_empty());
+        assert!(select(&mut s, "select * from t1 where a = 4;")?.is_empty());
+        assert!(s.execute("select * from t1 where d = 1;").is_err());
+        assert!(s.execute("select * from t1 where a;").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_table_where() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        s.execute("create table t1 (a int primary key, b text, c bigint);")?;
+        s.execute("insert into t1 values (1, 'a', 10), (2, null, 20), (3, 'a', 10);")?;
+
+        let txn = kv_engine.begin()?;
+        let all = txn.scan_table("t1".to_string())?;
+        for (col_index, value) in [
+            (0, Value::Integer(2)),
+            (1, Value::String("a".to_string())),
+            (1, Value::Null),
+            (2, Value::Integer(10)),
+            (2, Value::BigInt(20)),
+            (2, Value::Integer(5)),
+        ] {
+            let rows = txn.scan_table_where("t1".to_string(), col_index, &value)?;
+            let expect: Vec<_> = all
+                .iter()
+                .filter(|row| row[col_index] != Value::Null && value != Value::Null)
+                .filter(|row| row[col_index].compare(&value).unwrap().is_eq())
+                .cloned()
+                .collect();
+            assert_eq!(rows, expect);
+            assert!(rows.len() < all.len());
+        }
+        assert!(txn
+            .scan_table_where("t1".to_string(), 1, &Value::Integer(1))
+            .is_err());
+
+        // The pushed down equality returns the same rows as the filter after the scan.
+        for (pushed, filtered) in [
+            ("a = 2", "a = 2 and true"),
+            ("'a' = b", "'a' = b and true"),
+            ("c = 10", "c = 10 and true"),
+            ("b = null", "b = null and true"),
+        ] {
+            let pushed = s.execute(&format!("select * from t1 where {};", pushed))?;
+            let filtered = s.execute(&format!("select * from t1 where {};", filtered))?;
+            assert_eq!(format!("{:?}", pushed), format!("{:?}", filtered));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_where_comparison() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        s.execute("create table t1 (a int primary key, b float, c text);")?;
+        s.execute("insert into t1 values (1, 1.5, 'x'), (2, null, 'y'), (3, 3.0, 'z');")?;
+
+        let count = |s: &mut Session<_>, filter: &str| -> Result<usize> {
+            match s.execute(&format!("select * from t1 where {};", filter))? {
+                ResultSet::Scan { rows, .. } => Ok(rows.len()),
+                r => panic!("unexpected result {:?}", r),
+            }
+        };
+        assert_eq!(count(&mut s, "a > 1")?, 2);
+        assert_eq!(count(&mut s, "a >= 1")?, 3);
+        assert_eq!(count(&mut s, "a < 3")?, 2);
+        assert_eq!(count(&mut s, "a <= 1")?, 1);
+        assert_eq!(count(&mut s, "a != 2")?, 2);
+        assert_eq!(count(&mut s, "a <> 2")?, 2);
+        assert_eq!(count(&mut s, "b > 1")?, 2);
+        assert_eq!(count(&mut s, "b = a")?, 1);
+        assert_eq!(count(&mut s, "b != 0")?, 2);
+        assert_eq!(count(&mut s, "c >= 'y'")?, 2);
+        assert!(count(&mut s, "c > 1").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_where_logic() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        s.execute("create table t1 (a int primary key, b float, c text);")?;
+        s.execute("insert into t1 values (1, 1.5, 'x'), (2, null, 'y'), (3, 3.0, 'z');")?;
+
+        let count = |s: &mut Session<_>, filter: &str| -> Result<usize> {
+            match s.execute(&format!("select * from t1 where {};", filter))? {
+                ResultSet::Scan { rows, .. } => Ok(rows.len()),
+                r => panic!("unexpected result {:?}", r),
+            }
+        };
+        assert_eq!(count(&mut s, "a = 1 and c = 'x'")?, 1);
+        assert_eq!(count(&mut s, "a = 1 or a = 3")?, 2);
+        assert_eq!(count(&mut s, "a = 1 and c = 'y' or a = 3")?, 1);
+        assert_eq!(count(&mut s, "not a = 1")?, 2);
+        assert_eq!(count(&mut s, "not (a = 1 or a = 2)")?, 1);
+        // Comparisons with the NULL in row 2 are unknown.
+        assert_eq!(count(&mut s, "b > 2 or a = 2")?, 2);
+        assert_eq!(count(&mut s, "b > 2 or a = 9")?, 1);
+        assert_eq!(count(&mut s, "b > 2 and a = 2")?, 0);
+        assert_eq!(count(&mut s, "not b > 2")?, 1);
+        assert!(count(&mut s, "a and c = 'x'").is_err());
+        assert!(count(&mut s, "not c").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_projection() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        s.execute("create table t1 (a int primary key, b text, c bigint);")?;
+        s.execute("insert into t1 values (1, 'a', 10), (2, 'b', 20);")?;
+
+        match s.execute("select c, a from t1 where b = 'b';")? {
+            ResultSet::Scan { columns, rows, .. } => {
+                assert_eq!(columns, vec!["c".to_string(), "a".to_string()]);
+                assert_eq!(rows, vec![vec![Value::BigInt(20), Value::Integer(2)]]);
+            }
+            r => panic!("unexpected result {:?}", r),
+        }
+        assert_eq!(
+            s.execute("select a, d from t1;").unwrap_err(),
+            Error::Parse("column d does not exist".to_string())
+        );
+        assert!(s.execute("select a, from t1;").is_err());
+
+        match s.execute("select a * 10, c - a from t1 where a = 1;")? {
+            ResultSet::Scan { columns, rows, .. } => {
+                assert_eq!(columns, vec!["a * 10".to_string(), "c - a".to_string()]);
+                assert_eq!(rows, vec![vec![Value::Integer(10), Value::BigInt(9)]]);
+            }
+            r => panic!("unexpected result {:?}", r),
+        }
+        assert_eq!(
+            s.execute("select a + d from t1 where a = 3;").unwrap_err(),
+            Error::Parse("column d does not exist".to_string())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_without_from() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        match s.execute("select 1 + 1, 'hello', -2.5 * 2;")? {
+            ResultSet::Scan { columns, rows, .. } => {
+                assert_eq!(columns, vec!["1 + 1", "'hello'", "(-2.5) * 2"]);
+                assert_eq!(
+                    rows,
+                    vec![vec![
+                        Value::Integer(2),
+                        Value::String("hello".to_string()),
+                        Value::Float(-5.0),
+                    ]]
+                );
+            }
+            r => panic!("unexpected result {:?}", r),
+        }
+        match s.execute("select 1 where 1 > 2;")? {
+            ResultSet::Scan { rows, .. } => assert!(rows.is_empty()),
+            r => panic!("unexpected result {:?}", r),
+        }
+
+        for sql in [
+            "select a;",
+            "select *;",
+            "select 1 for update;",
+            "select 1 / 0;",
+        ] {
+            assert!(s.execute(sql).is_err(), "{}", sql);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_missing_table() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        s.execute("create table t1 (a int primary key, b text);")?;
+
+        match s.execute("select * from t2;") {
+            Err(e) => assert_eq!(e, Error::Internal("table t2 does not exist".to_string())),
+            r => panic!("unexpected result {:?}", r),
+        }
+        assert!(s.execute("select a from t2 where a = 1;").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_column_names() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        s.execute("create table t (z int primary key, a text, m float);")?;
+
+        // Names are in definition order, not sorted, also while the table is empty.
+        let scan = |s: &mut Session<_>| match s.execute("select * from t;") {
+            Ok(ResultSet::Scan { columns, rows, .. }) => (columns, rows.len()),
+            r => panic!("unexpected result {:?}", r),
+        };
+        let columns = vec!["z".to_string(), "a".to_string(), "m".to_string()];
+        assert_eq!(scan(&mut s), (columns.clone(), 0));
+        s.execute("insert into t values (1, 'a', 1.5);")?;
+        assert_eq!(scan(&mut s), (columns, 1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_count() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        s.execute("create table t1 (a int primary key, b text);")?;
+
+        match s.execute("select count(*) from t1;")? {
+            ResultSet::Scan { columns, rows, .. } => {
+                assert_eq!(columns, vec!["COUNT(*)"]);
+                assert_eq!(rows, vec![vec![Value::Integer(0)]]);
+            }
+            r => panic!("unexpected result {:?}", r),
+        }
+
+        s.execute("insert into t1 values (1, 'a'), (2, null), (3, 'a');")?;
+        match s.execute("select COUNT(*), count(b), count(*) * 2 from t1;")? {
+            ResultSet::Scan { columns, rows, .. } => {
+                assert_eq!(columns, vec!["COUNT(*)", "COUNT(b)", "COUNT(*) * 2"]);
+                assert_eq!(
+                    rows,
+                    vec![vec![
+                        Value::Integer(3),
+                        Value::Integer(2),
+                        Value::Integer(6)
+                    ]]
+                );
+            }
+            r => panic!("unexpected result {:?}", r),
+        }
+        match s.execute("select count(*) from t1 where b = 'a';")? {
+            ResultSet::Scan { rows, .. } => assert_eq!(rows, vec![vec![Value::Integer(2)]]),
+            r => panic!("unexpected result {:?}", r),
+        }
+        match s.execute("select count(*);")? {
+            ResultSet::Scan { rows, .. } => assert_eq!(rows, vec![vec![Value::Integer(1)]]),
+            r => panic!("unexpected result {:?}", r),
+        }
+
+        assert!(s.execute("select foo(*) from t1;").is_err());
+        assert!(s.execute("select count(c) from t1;").is_err());
+        assert!(s.execute("select a, count(*) from t1;").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_aggregates() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        s.execute("create table t1 (a int primary key, b int, c float, d text);")?;
+
+        let sql = "select sum(b), avg(b), min(b), max(b), sum(c), avg(c) from t1;";
+        assert_eq!(select(&mut s, sql)?, vec![vec![Value::Null; 6]]);
+
+        s.execute("insert into t1 values (1, 4, 1.5, 'b'), (2, null, null, null);")?;
+        s.execute("insert into t1 values (3, -1, 0.5, 'a'), (4, 3, 2.5, 'c');")?;
+        let sql = "select sum(b), avg(b), min(b), max(b) from t1;";
+        assert_eq!(
+            select(&mut s, sql)?,
+            vec![vec![
+                Value::Integer(6),
+                Value::Float(2.0),
+                Value::Integer(-1),
+                Value::Integer(4)
+            ]]
+        );
+        let sql = "select sum(c), avg(c), min(c), max(c) from t1;";
+        assert_eq!(
+            select(&mut s, sql)?,
+            vec![vec![
+                Value::Float(4.5),
+                Value::Float(1.5),
+                Value::Float(0.5),
+                Value::Float(2.5)
+            ]]
+        );
+        let sql = "select min(d), max(d), sum(b + c), avg(a) from t1;";
+        assert_eq!(
+            select(&mut s, sql)?,
+            vec![vec![
+                Value::String("a".to_string()),
+                Value::String("c".to_string()),
+                Value::Float(10.5),
+                Value::Float(2.5)
+            ]]
+        );
+        let sql = "select avg(b), max(c) from t1 where a = 2;";
+        assert_eq!(select(&mut s, sql)?, vec![vec![Value::Null, Value::Null]]);
+
+        assert!(s.execute("select sum(d) from t1;").is_err());
+        assert!(s.execute("select avg(d) from t1;").is_err());
+        assert!(s.execute("select sum(b, c) from t1;").is_err());
+        assert!(s.execute("select max(*) from t1;").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_group_by() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        s.execute("create table t1 (a int primary key, b text, c int);")?;
+
+        // Without rows there are no groups, but unknown columns are still caught.
+        assert!(select(&mut s, "select b, count(*) from t1 group by b;")?.is_empty());
+        let sql = "select count(*) from t1 group by d;";
+        assert_eq!(
+            s.execute(sql).unwrap_err(),
+            Error::Parse("column d does not exist".to_string())
+        );
+
+        s.execute("insert into t1 values (1, 'x', 1), (2, 'y', 2), (3, 'x', 3);")?;
+        s.execute("insert into t1 values (4, null, 4), (5, 'y', null), (6, null, 6);")?;
+        let (x, y) = (
+            Value::String("x".to_string()),
+            Value::String("y".to_string()),
+        );
+        match s.execute("select b, count(*), sum(c) from t1 group by b order by b;")? {
+            ResultSet::Scan { columns, rows, .. } => {
+                assert_eq!(columns, vec!["b", "COUNT(*)", "SUM(c)"]);
+                assert_eq!(
+                    rows,
+                    vec![
+                        vec![Value::Null, Value::Integer(2), Value::Integer(10)],
+                        vec![x.clone(), Value::Integer(2), Value::Integer(4)],
+                        vec![y.clone(), Value::Integer(2), Value::Integer(2)],
+                    ]
+                );
+            }
+            r => panic!("unexpected result {:?}", r),
+        }
+
+        let sql = "select count(*) * 10, b from t1 where a < 4 group by b order by b desc;";
+        assert_eq!(
+            select(&mut s, sql)?,
+            vec![
+                vec![Value::Integer(10), y.clone()],
+                vec![Value::Integer(20), x.clone()],
+            ]
+        );
+        let sql = "select b, c, count(*) from t1 where a <= 3 group by b, c order by c;";
+        assert_eq!(select(&mut s, sql)?.len(), 3);
+        let sql = "select b from t1 group by b order by b limit 1 offset 1;";
+        assert_eq!(select(&mut s, sql)?, vec![vec![x]]);
+
+        for sql in [
+            "select a, count(*) from t1 group by b;",
+            "select a + 1 from t1 group by b;",
+            "select * from t1 group by b;",
+        ] {
+            match s.execute(sql) {
+                Err(Error::Internal(msg)) => assert!(msg.contains("must be grouped"), "{}", msg),
+                r => panic!("unexpected result {:?}", r),
+            }
+        }
+        assert!(s.execute("select d from t1 group by d;").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_custom_aggregate() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        s.execute("create table t1 (a int primary key, b int);")?;
+        s.execute("insert into t1 values (1, 2), (2, null), (3, 5);")?;
+
+        assert!(s.execute("select product(b) from t1;").is_err());
+        // Multiplies the non-NULL values, NULL without any.
+        s.register_aggregate(
+            "product",
+            || None,
+            |product: &mut Option<i64>, args| {
+                match &args[0] {
+                    Value::Null => {}
+                    Value::Integer(i) => *product = Some(product.unwrap_or(1) * i),
+                    v => return Err(Error::Internal(format!("cannot multiply {}", v))),
+                }
+                Ok(())
+            },
+            |product| Ok(product.map_or(Value::Null, Value::Integer)),
+        );
+        // The cached plan of the failed query looks the aggregate up when executed.
+        assert_eq!(
+            select(&mut s, "select product(b) from t1;")?,
+            vec![vec![Value::Integer(10)]]
+        );
+        // Plans only hold the aggregate's name, so they can be sent across threads.
+        fn assert_send<T: Send>(_: &T) {}
+        assert_send(&Planner::new().build(Parser::new("select product(b) from t1;").parse()?)?);
+
+        match s.execute("select PRODUCT(b), count(*), product(a) + 1 from t1;")? {
+            ResultSet::Scan { columns, rows, .. } => {
+                assert_eq!(columns, vec!["PRODUCT(b)", "COUNT(*)", "PRODUCT(a) + 1"]);
+                assert_eq!(
+                    rows,
+                    vec![vec![
+                        Value::Integer(10),
+                        Value::Integer(3),
+                        Value::Integer(7)
+                    ]]
+                );
+            }
+            r => panic!("unexpected result {:?}", r),
+        }
+        s.execute("create table t2 (a int primary key, g text, v int);")?;
+        s.execute("insert into t2 values (1, 'x', 2), (2, 'y', 3), (3, 'x', 4), (4, 'y', 5);")?;
+        match s.execute("select g, product(v) from t2 group by g order by g;")? {
+            ResultSet::Scan { rows, .. } => assert_eq!(
+                rows,
+                vec![
+                    vec![Value::String("x".to_string()), Value::Integer(8)],
+                    vec![Value::String("y".to_string()), Value::Integer(15)],
+                ]
+            ),
+            r => panic!("unexpected result {:?}", r),
+        }
+        match s.execute("select product(b) from t1 where a = 2;")? {
+            ResultSet::Scan { rows, .. } => assert_eq!(rows, vec![vec![Value::Null]]),
+            r => panic!("unexpected result {:?}", r),
+        }
+        assert!(s.execute("select product('x');").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_any_all() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        s.execute("create table t1 (a int primary key);")?;
+        s.execute("create table t2 (b int primary key, c int);")?;
+        s.execute("insert into t1 values (1), (2), (3), (4);")?;
+        s.execute("insert into t2 values (1, 2), (2, 3), (3, null);")?;
+
+        let ints = |ints: &[i64]| {
+            ints.iter()
+                .map(|i| vec![Value::Integer(*i)])
+                .collect::<Vec<_>>()
+        };
+
+        let sql = "select a from t1 where a > all (select b from t2 where b < 3);";
+        assert_eq!(select(&mut s, sql)?, ints(&[3, 4]));
+        let sql = "select a from t1 where a = any (select c from t2);";
+        assert_eq!(select(&mut s, sql)?, ints(&[2, 3]));
+        let sql = "select a from t1 where a <= any (select b + 1 from t2) and a != 1;";
+        assert_eq!(select(&mut s, sql)?, ints(&[2, 3, 4]));
+        let sql = "select a from t1 where not a < all (select b from t2 where b > 1);";
+        assert_eq!(select(&mut s, sql)?, ints(&[2, 3, 4]));
+        let sql = "select a from t1 where (a = any (select c from t2)) = true;";
+        assert_eq!(select(&mut s, sql)?, ints(&[2, 3]));
+
+        // A NULL makes ALL unknown unless another value fails, and ANY unknown unless
+        // another value matches.
+        let sql = "select a from t1 where a > all (select c from t2);";
+        assert_eq!(select(&mut s, sql)?, ints(&[]));
+        let sql = "select a from t1 where a < all (select c from t2);";
+        assert_eq!(select(&mut s, sql)?, ints(&[]));
+        let sql = "select a from t1 where not a = any (select c from t2);";
+        assert_eq!(select(&mut s, sql)?, ints(&[]));
+
+        // Over no values ALL holds and ANY doesn't.
+        let sql = "select a from t1 where a > all (select b from t2 where b > 5);";
+        assert_eq!(select(&mut s, sql)?, ints(&[1, 2, 3, 4]));
+        let sql = "select a from t1 where a = any (select b from t2 where b > 5);";
+        assert_eq!(select(&mut s, sql)?, ints(&[]));
+
+        match s.execute("delete from t1 where a = any (select c from t2);")? {
+            ResultSet::Delete { count } => assert_eq!(count, 2),
+            r => panic!("unexpected result {:?}", r),
+        }
+        assert!(s
+            .execute("select a from t1 where a = any (select * from t2);")
+            .is_err());
+        assert!(s
+            .execute("select a = any (select b from t2) from t1;")
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_order_by() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        s.execute("create table t1 (a int primary key, b text, c float);")?;
+        s.execute("insert into t1 values (1, 'b', 2.5), (2, null, 1.0), (3, 'a', 2.5);")?;
+
+        let (v1, v2, v3) = (Value::Integer(1), Value::Integer(2), Value::Integer(3));
+        let sql = "select a from t1 order by b;";
+        assert_eq!(
+            select(&mut s, sql)?,
+            vec![vec![v2.clone()], vec![v3.clone()], vec![v1.clone()]]
+        );
+        let sql = "select a from t1 order by b desc;";
+        assert_eq!(
+            select(&mut s, sql)?,
+            vec![vec![v1.clone()], vec![v3.clone()], vec![v2.clone()]]
+        );
+        let sql = "select a from t1 where a > 1 order by c asc, a desc;";
+        assert_eq!(
+            select(&mut s, sql)?,
+            vec![vec![v2.clone()], vec![v3.clone()]]
+        );
+        let sql = "select a from t1 order by c desc, b;";
+        assert_eq!(select(&mut s, sql)?, vec![vec![v3], vec![v1], vec![v2]]);
+        assert!(s.execute("select * from t1 order by;").is_err());
+
+        // Unknown columns are caught when planning, also after grouping.
+        let unknown = Error::Parse("column d does not exist".to_string());
+        assert_eq!(
+            s.execute("select * from t1 order by d;").unwrap_err(),
+            unknown
+        );
+        let sql = "select b, count(*) from t1 group by b order by a;";
+        assert_eq!(
+            s.execute(sql).unwrap_err(),
+            Error::Parse("column a does not exist".to_string())
+        );
+        let plan = Planner::new()
+            .with_tables(kv_engine.begin()?.scan_tables()?)
+            .build(Parser::new("select a from t1 order by d;").parse()?);
+        assert_eq!(plan.err(), Some(unknown));
+
+        // Values of incompatible types cannot be ordered.
+        let mut txn = kv_engine.begin()?;
+        let row = vec![Value::Integer(4), Value::Integer(4), Value::Null];
+        txn.load_rows_unchecked("t1".to_string(), vec![row])?;
+        txn.commit()?;
+        assert!(matches!(
+            s.execute("select * from t1 order by b;"),
+            Err(Error::Internal(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_order_by_ties() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        s.execute("create table t1 (a int primary key, b int);")?;
+        for a in 0..200 {
+            s.execute(&format!("insert into t1 values ({}, {});", a, a % 3))?;
+        }
+
+        // Tied rows keep the order of their primary keys, as stored.
+        let keys = |b: i64| (0..200).filter(move |a| a % 3 == b);
+        let rows = |keys: Vec<i64>| -> Vec<Row> {
+            keys.into_iter().map(|a| vec![Value::Integer(a)]).collect()
+        };
+        let asc = rows(keys(0).chain(keys(1)).chain(keys(2)).collect());
+        let desc = rows(keys(2).chain(keys(1)).chain(keys(0)).collect());
+        for _ in 0..3 {
+            assert_eq!(select(&mut s, "select a from t1 order by b;")?, asc);
+            assert_eq!(select(&mut s, "select a from t1 order by b desc;")?, desc);
+        }
+        let sql = "select a from t1 order by b limit 5 offset 10;";
+        assert_eq!(select(&mut s, sql)?, rows(vec![30, 33, 36, 39, 42]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_limit_offset() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        s.execute("create table t1 (a int primary key, b int);")?;
+        s.execute("insert into t1 values (1, 40), (2, 30), (3, 20), (4, 10);")?;
+
+        let v = |a| vec![Value::Integer(a)];
+        let sql = "select a from t1 limit 2;";
+        assert_eq!(select(&mut s, sql)?, vec![v(1), v(2)]);
+        let sql = "select a from t1 order by b limit 2 offset 1;";
+        assert_eq!(select(&mut s, sql)?, vec![v(3), v(2)]);
+        let sql = "select a from t1 where a > 1 offset 2;";
+        assert_eq!(select(&mut s, sql)?, vec![v(4)]);
+        assert!(select(&mut s, "select * from t1 offset 5;")?.is_empty());
+        assert_eq!(select(&mut s, "select * from t1 limit 10;")?.len(), 4);
+
+        match s.execute("select b from t1 limit 0;")? {
+            ResultSet::Scan { columns, rows, .. } => {
+                assert_eq!(columns, vec!["b".to_string()]);
+                assert!(rows.is_empty());
+            }
+            r => panic!("unexpected result {:?}", r),
+        }
+        assert!(s.execute("select * from t1 limit -1;").is_err());
+        assert!(s.execute("select * from t1 limit 1.5;").is_err());
+        assert!(s.execute("select * from t1 offset 1 limit 1;").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_for_update() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        s.execute("create table t1 (a int primary key, b int);")?;
+        s.execute("insert into t1 values (1, 10), (2, 20);")?;
+
+        let plan = |sql: &str| Plan::build(Parser::new(sql).parse()?);
+        let ctx = ExecContext::default();
+        let mut txn1 = kv_engine.begin()?;
+        plan("select a from t1 where a = 1 for update;")?.execute(&mut txn1, &ctx)?;
+
+        // The locked row conflicts, the other one is free to change.
+        let mut txn2 = kv_engine.begin()?;
+        let update = plan("update t1 set b = 11 where a = 1;")?.execute(&mut txn2, &ctx);
+        assert!(matches!(update, Err(Error::WriteConflict)));
+        txn2.rollback()?;
+        let mut txn3 = kv_engine.begin()?;
+        plan("update t1 set b = 21 where a = 2;")?.execute(&mut txn3, &ctx)?;
+        txn3.commit()?;
+
+        // Once the lock holder commits, the row can be written again.
+        txn1.commit()?;
+        s.execute("update t1 set b = 11 where a = 1;")?;
+        match s.execute("select * from t1;")? {
+            ResultSet::Scan { rows, .. } => assert_eq!(
+                rows,
+                vec![
+                    vec![Value::Integer(1), Value::Integer(11)],
+                    vec![Value::Integer(2), Value::Integer(21)],
+                ]
+            ),
+            r => panic!("unexpected result {:?}", r),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_arithmetic() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        s.execute("create table t1 (a int primary key, b int default 2 * 3, c float);")?;
+        s.execute("insert into t1 values (1 + 2 * 3, 10 / 3, 7 / 2.0), (2, 5, 1.5);")?;
+        s.execute("insert into t1 (a) values (3);")?;
+
+        match s.execute("select * from t1 where b - a > 0 order by a;")? {
+            ResultSet::Scan { rows, .. } => assert_eq!(
+                rows,
+                vec![
+                    vec![Value::Integer(2), Value::Integer(5), Value::Float(1.5)],
+                    vec![Value::Integer(3), Value::Integer(6), Value::Null],
+                ]
+            ),
+            r => panic!("unexpected result {:?}", r),
+        }
+        s.execute("update t1 set b = b * 2 + a where a = 7;")?;
+        match s.execute("select b, c from t1 where a = 7;")? {
+            ResultSet::Scan { rows, .. } => {
+                assert_eq!(rows, vec![vec![Value::Integer(13), Value::Float(3.5)]])
+            }
+            r => panic!("unexpected result {:?}", r),
+        }
+        assert!(s
+            .execute("insert into t1 values (4, 1 / 0, null);")
+            .is_err());
+        assert!(s
+            .execute("select * from t1 where a / (b - 5) = 1;")
+            .is_err());
+        assert!(s
+            .execute("insert into t1 values (4, a + 1, null);")
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_duplicate_column() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        assert_eq!(
+            s.execute("create table t1 (a int primary key, b text, A text);")
+                .err(),
+            Some(Error::Internal("duplicate column a".to_string()))
+        );
+        assert!(!kv_engine.begin()?.table_exists("t1".to_string())?);
+        s.execute("create table t1 (a int primary key, b text);")?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_delete() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        s.execute("create table t1 (a int primary key, b text);")?;
+        s.execute("insert into t1 values (1, 'a'), (2, 'b'), (3, 'a');")?;
+
+        match s.execute("delete from t1 where b = 'a';")? {
+            ResultSet::Delete { count } => assert_eq!(count, 2),
+            r => panic!("unexpected result {:?}", r),
+        }
+        match s.execute("select * from t1;")? {
+            ResultSet::Scan { rows, .. } => assert_eq!(
+                rows,
+                vec![vec![Value::Integer(2), Value::String("b".to_string())]]
+            ),
+            r => panic!("unexpected result {:?}", r),
+        }
+        assert_eq!(kv_engine.begin()?.row_count("t1".to_string())?, 1);
+
+        match s.execute("delete from t1 where a > 5;")? {
+            ResultSet::Delete { count } => assert_eq!(count, 0),
+            r => panic!("unexpected result {:?}", r),
+        }
+        match s.execute("delete from t1;")? {
+            ResultSet::Delete { count } => assert_eq!(count, 1),
+            r => panic!("unexpected result {:?}", r),
+        }
+        assert!(kv_engine.begin()?.scan_table("t1".to_string())?.is_empty());
+        assert!(s.execute("delete from t2;").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_update() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        s.execute("create table t1 (a int primary key, b text, c bigint not null);")?;
+        s.execute("insert into t1 values (1, 'a', 10), (2, 'b', 20), (3, 'a', 30);")?;
+
+        match s.execute("update t1 set b = 'c', c = 5 where b = 'a';")? {
+            ResultSet::Update { count } => assert_eq!(count, 2),
+            r => panic!("unexpected result {:?}", r),
+        }
+        match s.execute("update t1 set a = 4, c = a where a = 2;")? {
+            ResultSet::Update { count } => assert_eq!(count, 1),
+            r => panic!("unexpected result {:?}", r),
+        }
+        assert!(s.execute("update t1 set b = 1;").is_err());
+        assert!(s.execute("update t1 set c = null;").is_err());
+        assert!(s.execute("update t1 set d = 1;").is_err());
+
+        let txn = kv_engine.begin()?;
+        assert_eq!(
+            txn.scan_table("t1".to_string())?,
+            vec![
+                vec![
+                    Value::Integer(1),
+                    Value::String("c".to_string()),
+                    Value::BigInt(5)
+                ],
+                vec![
+                    Value::Integer(3),
+                    Value::String("c".to_string()),
+                    Value::BigInt(5)
+                ],
+                vec![
+                    Value::Integer(4),
+                    Value::String("b".to_string()),
+                    Value::BigInt(2)
+                ],
+            ]
+        );
+        assert_eq!(txn.row_count("t1".to_string())?, 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_multi_line_statement() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+
+        // Lines are buffered like in a REPL until they hold a whole statement.
+        let mut buffer = String::new();
+        let mut results = Vec::new();
+        for line in [
+            "create table t1 (",
+            "  a int primary key, -- the key;",
+            "  b text);",
+        ] {
+            buffer.push_str(line);
+            buffer.push('\n');
+            if is_statement_complete(&buffer) {
+                results.push(s.execute(&buffer)?);
+                buffer.clear();
+            }
+        }
+
+        assert_eq!(results.len(), 1);
+        assert!(kv_engine.begin()?.table_exists("t1".to_string())?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_fixtures() -> Result<()> {
+        let column = |name: &str, datatype, primary_key| Column {
+            name: name.to_string(),
+            datatype,
+            nullable: false,
+            default: None,
+            comment: None,
+            primary_key,
+        };
+        let users = Table {
+            name: "users".to_string(),
+            columns: vec![
+                column("id", DataType::BigInt, true),
+                column("name", DataType::String, false),
+            ],
+        };
+        let orders = Table {
+            name: "orders".to_string(),
+            columns: vec![
+                column("id", DataType::Integer, true),
+                column("user_id", DataType::BigInt, false),
+            ],
+        };
+        let kv_engine = KvEngine::from_fixtures(vec![
+            (
+                users,
+                vec![
+                    vec![Value::Integer(1), Value::String("a".to_string())],
+                    vec![Value::BigInt(2), Value::String("b".to_string())],
+                ],
+            ),
+            (orders, vec![vec![Value::Integer(10), Value::BigInt(2)]]),
+        ])?;
+
+        let mut s = kv_engine.session()?;
+        match s.execute("select name from users where id = 2;")? {
+            ResultSet::Scan { rows, .. } => {
+                assert_eq!(rows, vec![vec![Value::String("b".to_string())]])
+            }
+            r => panic!("unexpected result {:?}", r),
+        }
+        match s.execute("select * from orders;")? {
+            ResultSet::Scan { rows, .. } => {
+                assert_eq!(rows, vec![vec![Value::Integer(10), Value::BigInt(2)]])
+            }
+            r => panic!("unexpected result {:?}", r),
+        }
+        assert_eq!(kv_engine.begin()?.row_count("users".to_string())?, 2);
+
+        // Rows are still checked against the schema.
+        let table = || Table {
+            name: "t1".to_string(),
+            columns: vec![
+                column("a", DataType::Integer, true),
+                column("b", DataType::String, false),
+            ],
+        };
+        let rows = vec![vec![Value::Integer(1), Value::Null]];
+        assert!(KvEngine::from_fixtures(vec![(table(), rows)]).is_err());
+        let rows = vec![vec![Value::Integer(1), Value::Integer(2)]];
+        assert!(KvEngine::from_fixtures(vec![(table(), rows)]).is_err());
+        let rows = vec![vec![Value::Integer(1)]];
+        assert!(KvEngine::from_fixtures(vec![(table(), rows)]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_result_set_display() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        let result = s.execute("create table t1 (id int primary key, name text, c float);")?;
+        assert_eq!(result.to_string(), "Table t1 created");
+        let sql = "insert into t1 values (1, 'alice', 1.0), (20, null, 2.5);";
+        assert_eq!(s.execute(sql)?.to_string(), "2 rows inserted");
+
+        assert_eq!(
+            s.execute("select * from t1;")?.to_string(),
+            [
+                "id | name  | c",
+                "---+-------+----",
+                "1  | alice | 1.0",
+                "20 | NULL  | 2.5",
+            ]
+            .join("\n")
+        );
+        let sql = "select name from t1 where id = 3;";
+        assert_eq!(s.execute(sql)?.to_string(), "name\n----");
+        let sql = "update t1 set c = 3.0 where id = 1;";
+        assert_eq!(s.execute(sql)?.to_string(), "1 rows updated");
+        assert_eq!(s.execute("delete from t1;")?.to_string(), "2 rows deleted");
+        Ok(())
+    }
+
+    #[test]
+    fn test_plan_cache() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        s.execute("create table t1 (a int primary key, b text);")?;
+        s.execute("insert into t1 values (1, 'a'), (2, 'A');")?;
+
+        // Cached plans see the current data.
+        let select = "select a from t1 where b = 'a';";
+        s.execute(select)?;
+        assert!(s.plans.get(&normalize(select)?).is_some());
+        s.execute("insert into t1 values (3, 'a');")?;
+        match s.execute(select)? {
+            ResultSet::Scan { rows, .. } => assert_eq!(rows.len(), 2),
+            r => panic!("unexpected result {:?}", r),
+        }
+
+        // DDL invalidates the cached plans, also those of other sessions.
+        let mut other = kv_engine.session()?;
+        other.execute(select)?;
+        s.execute("create table t2 (a int primary key);")?;
+        other.execute("select * from t2;")?;
+        assert!(other.plans.get(&normalize(select)?).is_none());
+        s.execute("create temp table t1 (a int primary key);")?;
+        assert!(s.plans.get(&normalize(select)?).is_none());
+        match s.execute(select)? {
+            ResultSet::Scan { rows, .. } => assert!(rows.is_empty()),
+            r => panic!("unexpected result {:?}", r),
+        }
+
+        // Whitespace, comments and keyword case do not matter, string literals do.
+        let mut cache = PlanCache::new(2);
+        let plan = || Plan::build(Parser::new("select * from t1;").parse().unwrap()).unwrap();
+        cache.insert(normalize(select)?, Some("t1".to_string()), plan());
+        let sql = "SELECT a\n  FROM t1 /* again */ WHERE b = 'a';";
+        assert!(cache.get(&normalize(sql)?).is_some());
+        assert!(cache
+            .get(&normalize("select a from t1 where b = 'A';")?)
+            .is_none());
+        assert!(cache
+            .get(&normalize("select a from t1 where b = a;")?)
+            .is_none());
+
+        // The least recently used plan is evicted.
+        let (a, b, c) = (
+            normalize("select 1;")?,
+            normalize("select 2;")?,
+            normalize("select 3;")?,
+        );
+        let mut cache = PlanCache::new(2);
+        cache.insert(a.clone(), None, plan());
+        cache.insert(b.clone(), None, plan());
+        assert!(cache.get(&a).is_some());
+        cache.insert(c.clone(), None, plan());
+        assert!(cache.get(&a).is_some());
+        assert!(cache.get(&b).is_none());
+        assert!(cache.get(&c).is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_session_relaxed_keywords() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        let sql = "create table t1 (key int primary key);";
+        assert!(s.execute(sql).is_err());
+
+        s.set_relaxed_keywords(vec![Keyword::Key]);
+        s.execute(sql)?;
+        s.execute("insert into t1 (key) values (1);")?;
+        match s.execute("select key from t1;")? {
+            ResultSet::Scan { columns, rows, .. } => {
+                assert_eq!(columns, vec!["key".to_string()]);
+                assert_eq!(rows, vec![vec![Value::Integer(1)]]);
+            }
+            r => panic!("unexpected result {:?}", r),
+        }
+
+        // A relaxed type keyword is a column unless it starts a typed literal.
+        s.set_relaxed_keywords(vec![Keyword::Text]);
+        s.execute("create table t2 (id int primary key, text text);")?;
+        s.execute("insert into t2 values (1, 'a'), (2, text 'b');")?;
+        assert_eq!(
+            select(&mut s, "select text from t2;")?,
+            vec![
+                vec![Value::String("a".to_string())],
+                vec![Value::String("b".to_string())]
+            ]
+        );
+        assert_eq!(
+            select(&mut s, "select id from t2 where text = text 'a';")?,
+            vec![vec![Value::Integer(1)]]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_session_default_datatype() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        let sql = "create table t1 (a int primary key, b);";
+        assert!(s.execute(sql).is_err());
+
+        s.set_default_datatype(Some(DataType::String));
+        s.execute(sql)?;
+        let table = kv_engine.begin()?.must_get_table("t1".to_string())?;
+        assert_eq!(table.columns[1].datatype, DataType::String);
+        s.execute("insert into t1 values (1, 'a');")?;
+        assert!(s.execute("insert into t1 values (2, 2);").is_err());
+
+        s.set_default_datatype(None);
+        assert!(s
+            .execute("create table t2 (a int primary key, b);")
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_session_max_identifier_length() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        s.execute("create table abcd (a int primary key);")?;
+        s.execute("select * from abcd;")?;
+
+        s.set_max_identifier_length(Some(3));
+        assert_eq!(
+            s.execute("select * from abcd;").unwrap_err(),
+            Error::Parse("[Parser] Identifier too long: abcd".to_string())
+        );
+        assert!(s
+            .execute("create table abcde (a int primary key);")
+            .is_err());
+        s.execute("create table abc (a int primary key);")?;
+
+        s.set_max_identifier_length(None);
+        s.execute("select * from abcd;")?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_session_integer_division() -> Result<()> {
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        s.execute("create table t1 (a int primary key, b int);")?;
+        s.execute("insert into t1 values (1, 7);")?;
+
+        let divide = |s: &mut Session<_>| match s.execute("select b / 2, 7 / 2 from t1;") {
+            Ok(ResultSet::Scan { rows, .. }) => rows,
+            r => panic!("unexpected result {:?}", r),
+        };
+        assert_eq!(
+            divide(&mut s),
+            vec![vec![Value::Integer(3), Value::Integer(3)]]
+        );
+
+        // The setting applies to cached plans too.
+        s.set_integer_division(IntegerDivision::Float);
+        assert_eq!(
+            divide(&mut s),
+            vec![vec![Value::Float(3.5), Value::Float(3.5)]]
+        );
+
+        s.set_integer_division(IntegerDivision::Truncate);
+        assert_eq!(
+            divide(&mut s),
+            vec![vec![Value::Integer(3), Value::Integer(3)]]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_table() -> Result<()> {
+        let create = "create table t1 (a int primary key, b text, c float, d bytes);";
+        let kv_engine = KvEngine::new(MemoryEngine::new());
+        let mut s = kv_engine.session()?;
+        s.execute(create)?;
+        s.execute(
+            "insert into t1 values (1, 'it''s a \"test\"', 1.0, x'00FF'), (2, 'a;\nb', null, null);",
+        )?;
+
+        let dump = s.dump_table("t1")?;
+        assert_eq!(
+            dump,
+            "INSERT INTO t1 VALUES (1, 'it''s a \"test\"', 1.0, x'00FF');\n\
+             INSERT INTO t1 VALUES (2, 'a;\nb', NULL, NULL);\n"
+        );
+        assert!(s.dump_table("t2").is_err());
+
+        // The dump recreates the rows intact.
+        let copy = KvEngine::new(MemoryEngine::new());
+        let mut s = copy.session()?;
+        s.execute(create)?;
+        assert_eq!(s.execute_batch(&dump)?.len(), 2);
+        assert_eq!(
+            copy.begin()?.scan_table("t1".to_string())?,
+            kv_engine.begin()?.scan_table("t1".to_string())?
+        );
+        Ok(())
+    }
 }
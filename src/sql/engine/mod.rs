@@ -1,28 +1,84 @@
-use crate::error::{Error, Result};
+use std::{collections::HashMap, sync::Arc};
 
-use super::{executor::ResultSet, parser::Parser, plan::Plan, schema::Table, types::Row};
+use crate::{
+    error::{Error, Result},
+    storage::memory::MemoryEngine,
+};
+
+use super::{
+    executor::{CustomAggregate, ExecContext, ResultSet},
+    parser::{
+        ast::{Consts, Statement},
+        normalize, statement_end, Keyword, NormalizedSql, Parser,
+    },
+    plan::{Plan, Planner},
+    schema::Table,
+    types::{DataType, IntegerDivision, Row, Value},
+};
 
 mod kv;
 
+pub use kv::{Change, ChangeOp, KvEngine};
+
 pub trait Engine: Clone {
-    type Transaction: Transaction;
+    type Transaction: Transaction + 'static;
 
     fn begin(&self) -> Result<Self::Transaction>;
 
+    // Incremented whenever a table is created, so that sessions can tell when the plans
+    // they cached may be stale.
+    fn schema_version(&self) -> Result<u64>;
+
     fn session(&self) -> Result<Session<Self>> {
         Ok(Session {
             engine: self.clone(),
+            temp: kv::KvEngine::new(MemoryEngine::new()),
+            logger: None,
+            txn: None,
+            aborted: None,
+            collect_insert_errors: false,
+            lenient_scan: false,
+            relaxed_keywords: Vec::new(),
+            default_datatype: None,
+            max_identifier_length: None,
+            ctx: ExecContext::default(),
+            plans: PlanCache::new(PLAN_CACHE_CAPACITY),
+            schema_version: 0,
         })
     }
 }
 
 pub trait Transaction {
+    // Identifies the transaction, later transactions have higher versions.
+    fn version(&self) -> u64;
     fn commit(&self) -> Result<()>;
     fn rollback(&self) -> Result<()>;
     fn create_row(&mut self, table: String, row: Row) -> Result<()>;
+    fn delete_row(&mut self, table_name: String, id: Value) -> Result<()>;
+    // Claims the row for this transaction, so that concurrent transactions writing it
+    // fail with a write conflict until this one commits or rolls back.
+    fn lock_row(&mut self, table_name: String, id: Value) -> Result<()>;
+    // Writes rows without any type or constraint checks. Only meant for bulk loading
+    // trusted data produced by this crate: invalid rows will corrupt the table.
+    fn load_rows_unchecked(&mut self, table_name: String, rows: Vec<Row>) -> Result<()>;
     fn scan_table(&self, table_name: String) -> Result<Vec<Row>>;
+    // Like scan_table, but skips the rows which can't be read, e.g. in a corrupt store,
+    // returning the primary keys of the skipped rows along with the others.
+    fn scan_table_lenient(&self, table_name: String) -> Result<(Vec<Row>, Vec<Value>)>;
+    // Rows whose column at col_index equals the value as with `=`, so NULL never matches.
+    // Rows are filtered while scanning instead of collecting the whole table first.
+    fn scan_table_where(
+        &self,
+        table_name: String,
+        col_index: usize,
+        value: &Value,
+    ) -> Result<Vec<Row>>;
+    // Number of rows in the table visible to the transaction.
+    fn row_count(&self, table_name: String) -> Result<u64>;
     fn create_table(&self, table: Table) -> Result<()>;
     fn get_table(&self, table_name: String) -> Result<Option<Table>>;
+    fn scan_tables(&self) -> Result<Vec<Table>>;
+    fn table_exists(&self, table_name: String) -> Result<bool>;
     fn must_get_table(&self, table_name: String) -> Result<Table> {
         self.get_table(table_name.clone())?.ok_or(Error::Internal(format!(
             "table {} does not exist",
@@ -31,25 +87,369 @@ pub trait Transaction {
     }
 }
 
+pub type StatementLogger = Box<dyn Fn(&str, &Result<ResultSet>)>;
+
+const PLAN_CACHE_CAPACITY: usize = 64;
+
+// Plans of recently executed statements keyed by their normalized SQL, together with the
+// name of the table they run against. Evicts the least recently used plan when full.
+struct PlanCache {
+    capacity: usize,
+    plans: HashMap<NormalizedSql, (u64, Option<String>, Plan)>,
+    clock: u64,
+}
+
+impl PlanCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            plans: HashMap::new(),
+            clock: 0,
+        }
+    }
+
+    fn get(&mut self, key: &NormalizedSql) -> Option<(Option<String>, Plan)> {
+        self.clock += 1;
+        let (last_used, table_name, plan) = self.plans.get_mut(key)?;
+        *last_used = self.clock;
+        Some((table_name.clone(), plan.clone()))
+    }
+
+    fn insert(&mut self, key: NormalizedSql, table_name: Option<String>, plan: Plan) {
+        if self.plans.len() >= self.capacity && !self.plans.contains_key(&key) {
+            let lru = self
+                .plans
+                .iter()
+                .min_by_key(|(_, (last_used, ..))| *last_used)
+                .map(|(key, _)| key.clone());
+            if let Some(lru) = lru {
+                self.plans.remove(&lru);
+            }
+        }
+        self.clock += 1;
+        self.plans.insert(key, (self.clock, table_name, plan));
+    }
+
+    fn clear(&mut self) {
+        self.plans.clear();
+    }
+}
+
 pub struct Session<E: Engine> {
     engine: E,
+    // Temporary tables only live in this session's own in-memory engine.
+    temp: kv::KvEngine<MemoryEngine>,
+    logger: Option<StatementLogger>,
+    // Transaction started by BEGIN, shared by the statements until COMMIT or ROLLBACK.
+    // Without one every statement runs in its own transaction.
+    txn: Option<E::Transaction>,
+    // Version of the transaction started by BEGIN that a failed statement rolled back.
+    // Later statements are rejected until COMMIT or ROLLBACK ends it.
+    aborted: Option<u64>,
+    collect_insert_errors: bool,
+    lenient_scan: bool,
+    relaxed_keywords: Vec<Keyword>,
+    default_datatype: Option<DataType>,
+    max_identifier_length: Option<usize>,
+    // Also holds the custom aggregates, which executors look up by name.
+    ctx: ExecContext,
+    plans: PlanCache,
+    // Schema version of the engine the cached plans were built against.
+    schema_version: u64,
 }
 
 impl<E: Engine> Session<E> {
+    // Registers a hook called with the SQL text and outcome of every executed statement.
+    pub fn set_statement_logger(&mut self, logger: StatementLogger) {
+        self.logger = Some(logger);
+    }
+
+    // When enabled, inserts skip invalid rows instead of failing, returning the errors of
+    // the skipped rows in ResultSet::Insert.
+    pub fn set_collect_insert_errors(&mut self, enabled: bool) {
+        self.collect_insert_errors = enabled;
+        self.plans.clear();
+    }
+
+    // When enabled, queries skip rows which can't be read instead of failing, so that a
+    // corrupt store is still partly queryable. The keys of the skipped rows are returned
+    // in ResultSet::Scan.
+    pub fn set_lenient_scan(&mut self, enabled: bool) {
+        self.lenient_scan = enabled;
+        self.plans.clear();
+    }
+
+    // Keywords accepted as table and column names in statements, see
+    // Parser::with_relaxed_keywords.
+    pub fn set_relaxed_keywords(&mut self, keywords: Vec<Keyword>) {
+        self.relaxed_keywords = keywords;
+        self.plans.clear();
+    }
+
+    // Datatype of columns created without one, see Parser::with_default_datatype.
+    pub fn set_default_datatype(&mut self, datatype: Option<DataType>) {
+        self.default_datatype = datatype;
+    }
+
+    // Longest table or column name accepted, see Parser::with_max_identifier_length.
+    // None keeps the parser's default.
+    pub fn set_max_identifier_length(&mut self, len: Option<usize>) {
+        self.max_identifier_length = len;
+        self.plans.clear();
+    }
+
+    // How `/` divides two integers, truncating by default. Column defaults are folded when
+    // the table is created and always truncate.
+    pub fn set_integer_division(&mut self, division: IntegerDivision) {
+        self.ctx.integer_division = division;
+    }
+
+    // Adds an aggregate function callable by name in queries, e.g. a MEDIAN. Each use
+    // starts from the state returned by init, folds the argument values of every row into
+    // it with accumulate and turns it into the result with finalize. Built-in aggregates
+    // can't be replaced.
+    pub fn register_aggregate<S: 'static>(
+        &mut self,
+        name: &str,
+        init: impl Fn() -> S + Send + Sync + 'static,
+        accumulate: impl Fn(&mut S, &[Value]) -> Result<()> + Send + Sync + 'static,
+        finalize: impl Fn(S) -> Result<Value> + Send + Sync + 'static,
+    ) {
+        let aggregate = CustomAggregate::new(init, accumulate, finalize);
+        Arc::make_mut(&mut self.ctx.aggregates).insert(name.to_uppercase(), aggregate);
+    }
+
+    // Serializes the schema of every table, e.g. for migrations and tooling.
+    pub fn export_schema(&self) -> Result<serde_json::Value> {
+        let txn = self.engine.begin()?;
+        Ok(serde_json::to_value(txn.scan_tables()?)?)
+    }
+
+    // Recreates the tables of an exported schema. Fails if any of them already exists.
+    pub fn import_schema(&self, schema: serde_json::Value) -> Result<()> {
+        let tables: Vec<Table> = serde_json::from_value(schema)
+            .map_err(|err| Error::Parse(format!("invalid schema: {}", err)))?;
+
+        let txn = self.engine.begin()?;
+        for table in tables {
+            let table_name = table.name.clone();
+            if let Err(err) = txn.create_table(table) {
+                txn.rollback()?;
+                return Err(Error::Internal(format!(
+                    "failed to import table {}: {:?}",
+                    table_name, err
+                )));
+            }
+        }
+        txn.commit()
+    }
+
+    // Checks every stored row of the table against the schema, e.g. after an unchecked load.
+    pub fn verify_table(&self, table_name: &str) -> Result<()> {
+        let txn = self.engine.begin()?;
+        let table = txn.must_get_table(table_name.to_string())?;
+        for row in txn.scan_table(table_name.to_string())? {
+            if let Err(err) = table.validate_row(&row) {
+                return Err(Error::Internal(format!(
+                    "table {} row {:?} is invalid: {:?}",
+                    table_name, row, err
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    // Renders the rows of the table as INSERT statements, one per line, which recreate
+    // them when run with execute_batch.
+    pub fn dump_table(&self, table_name: &str) -> Result<String> {
+        let txn = self.engine.begin()?;
+        txn.must_get_table(table_name.to_string())?;
+        let mut dump = String::new();
+        for row in txn.scan_table(table_name.to_string())? {
+            let stmt = Statement::Insert {
+                table_name: table_name.to_string(),
+                columns: None,
+                values: vec![row.into_iter().map(|v| Consts::from(v).into()).collect()],
+                replace: false,
+            };
+            dump += &stmt.to_sql();
+            dump.push('\n');
+        }
+        Ok(dump)
+    }
+
+    // Executes the `;` terminated statements of the input one after another, stopping at
+    // the first one that fails.
+    pub fn execute_batch(&mut self, sql: &str) -> Result<Vec<ResultSet>> {
+        let mut results = Vec::new();
+        let mut rest = sql;
+        while let Some(end) = statement_end(rest) {
+            results.push(self.execute(&rest[..end])?);
+            rest = &rest[end..];
+        }
+        // An unterminated statement fails to parse.
+        if !rest.trim().is_empty() {
+            results.push(self.execute(rest)?);
+        }
+        Ok(results)
+    }
+
     pub fn execute(&mut self, sql: &str) -> Result<ResultSet> {
-        match Parser::new(sql).parse()? {
-            stmt => {
-                let mut txn = self.engine.begin()?;
-                match Plan::build(stmt).execute(&mut txn) {
-                    Ok(result) => {
-                        txn.commit()?;
-                        Ok(result)
-                    }
-                    Err(err) => {
-                        txn.rollback()?;
-                        Err(err)
-                    }
+        let result = self.execute_statement(sql);
+        if let Some(logger) = &self.logger {
+            logger(sql, &result);
+        }
+        result
+    }
+
+    fn execute_statement(&mut self, sql: &str) -> Result<ResultSet> {
+        if let Some(version) = self.aborted {
+            return match self.parser(sql).parse()? {
+                // Nothing is left to commit, so COMMIT ends the transaction like ROLLBACK.
+                Statement::Commit | Statement::Rollback => {
+                    self.aborted = None;
+                    Ok(ResultSet::Rollback { version })
+                }
+                _ => Err(Error::Internal(
+                    "transaction aborted, statements are ignored until COMMIT or ROLLBACK"
+                        .to_string(),
+                )),
+            };
+        }
+        // DDL of other sessions invalidates the cached plans too.
+        let schema_version = self.engine.schema_version()?;
+        if schema_version != self.schema_version {
+            self.plans.clear();
+            self.schema_version = schema_version;
+        }
+
+        let key = normalize(sql)?;
+        let (create_temporary, table_name, plan) = match self.plans.get(&key) {
+            Some((table_name, plan)) => (None, table_name, plan),
+            None => {
+                let stmt = self.parser(sql).parse()?;
+                if let Statement::Begin | Statement::Commit | Statement::Rollback = stmt {
+                    return self.execute_transaction(stmt);
                 }
+
+                let create_temporary = match &stmt {
+                    Statement::CreateTable { temporary, .. } => Some(*temporary),
+                    _ => None,
+                };
+                let table_name = stmt.table_name().map(str::to_string);
+                let plan = self.build_plan(stmt)?;
+                // DDL is never cached, and invalidates the cached plans.
+                if create_temporary.is_some() {
+                    self.plans.clear();
+                } else {
+                    self.plans.insert(key, table_name.clone(), plan.clone());
+                }
+                (create_temporary, table_name, plan)
+            }
+        };
+
+        // Temporary tables shadow regular tables of the same name. They always run in
+        // their own transaction, even within BEGIN and COMMIT.
+        let temporary = match (create_temporary, table_name) {
+            (Some(temporary), _) => temporary,
+            (None, Some(table_name)) => self.temp.begin()?.table_exists(table_name)?,
+            (None, None) => false,
+        };
+        if temporary {
+            Self::execute_in(&self.temp, plan, &self.ctx)
+        } else {
+            self.execute_plan(plan)
+        }
+    }
+
+    fn execute_transaction(&mut self, stmt: Statement) -> Result<ResultSet> {
+        match (stmt, self.txn.take()) {
+            (Statement::Begin, None) => {
+                let txn = self.engine.begin()?;
+                let version = txn.version();
+                self.txn = Some(txn);
+                Ok(ResultSet::Begin { version })
+            }
+            (Statement::Begin, Some(txn)) => {
+                self.txn = Some(txn);
+                Err(Error::Internal("transaction already active".to_string()))
+            }
+            (Statement::Commit, Some(txn)) => {
+                txn.commit()?;
+                Ok(ResultSet::Commit {
+                    version: txn.version(),
+                })
+            }
+            (Statement::Rollback, Some(txn)) => {
+                txn.rollback()?;
+                Ok(ResultSet::Rollback {
+                    version: txn.version(),
+                })
+            }
+            (Statement::Commit | Statement::Rollback, None) => {
+                Err(Error::Internal("no active transaction".to_string()))
+            }
+            (stmt, txn) => {
+                self.txn = txn;
+                Err(Error::Internal(format!("unexpected statement {:?}", stmt)))
+            }
+        }
+    }
+
+    // Runs the plan in the transaction started by BEGIN, or in its own one without it.
+    fn execute_plan(&mut self, plan: Plan) -> Result<ResultSet> {
+        let txn = match self.txn.as_mut() {
+            Some(txn) => txn,
+            None => return Self::execute_in(&self.engine, plan, &self.ctx),
+        };
+        let result = plan.execute(txn, &self.ctx);
+        // The failed statement may have been partly applied, so the whole transaction
+        // is rolled back and stays aborted until the client ends it.
+        if result.is_err() {
+            if let Some(txn) = self.txn.take() {
+                self.aborted = Some(txn.version());
+                txn.rollback()?;
+            }
+        }
+        result
+    }
+
+    fn parser<'a>(&self, sql: &'a str) -> Parser<'a> {
+        let mut parser = Parser::new(sql).with_relaxed_keywords(self.relaxed_keywords.clone());
+        if let Some(datatype) = &self.default_datatype {
+            parser = parser.with_default_datatype(datatype.clone());
+        }
+        if let Some(len) = self.max_identifier_length {
+            parser = parser.with_max_identifier_length(len);
+        }
+        parser
+    }
+
+    fn build_plan(&self, stmt: Statement) -> Result<Plan> {
+        // Temporary tables shadow regular tables of the same name.
+        let mut tables = match &self.txn {
+            Some(txn) => txn.scan_tables()?,
+            None => self.engine.begin()?.scan_tables()?,
+        };
+        tables.extend(self.temp.begin()?.scan_tables()?);
+        Planner::new()
+            .with_collect_insert_errors(self.collect_insert_errors)
+            .with_lenient_scan(self.lenient_scan)
+            .with_tables(tables)
+            .build(stmt)
+    }
+
+    fn execute_in<T: Engine>(engine: &T, plan: Plan, ctx: &ExecContext) -> Result<ResultSet> {
+        let mut txn = engine.begin()?;
+        let result = plan.execute(&mut txn, ctx);
+        match result {
+            Ok(result) => {
+                txn.commit()?;
+                Ok(result)
+            }
+            Err(err) => {
+                txn.rollback()?;
+                Err(err)
             }
         }
     }
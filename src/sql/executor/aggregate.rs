@@ -0,0 +1,286 @@
+use std::{any::Any, cmp::Ordering, collections::HashMap, sync::Arc};
+
+use super::{evaluate, ExecContext, Executor, ResultSet};
+use crate::{
+    error::{Error, Result},
+    sql::{
+        engine::Transaction,
+        parser::ast::Expression,
+        types::{RowExt, Value},
+    },
+};
+
+type State = Box<dyn Any>;
+type AccumulateFn = dyn Fn(&mut State, &[Value]) -> Result<()> + Send + Sync;
+
+// An aggregate function added by the embedder, see Session::register_aggregate. The
+// state is opaque to the executor, which only passes it between the three functions.
+#[derive(Clone)]
+pub struct CustomAggregate {
+    init: Arc<dyn Fn() -> State + Send + Sync>,
+    accumulate: Arc<AccumulateFn>,
+    finalize: Arc<dyn Fn(State) -> Result<Value> + Send + Sync>,
+}
+
+impl CustomAggregate {
+    pub fn new<S: 'static>(
+        init: impl Fn() -> S + Send + Sync + 'static,
+        accumulate: impl Fn(&mut S, &[Value]) -> Result<()> + Send + Sync + 'static,
+        finalize: impl Fn(S) -> Result<Value> + Send + Sync + 'static,
+    ) -> Self {
+        let mismatch = || Error::Internal("aggregate state has an unexpected type".to_string());
+        Self {
+            init: Arc::new(move || Box::new(init())),
+            accumulate: Arc::new(move |state, args| {
+                accumulate(state.downcast_mut().ok_or_else(mismatch)?, args)
+            }),
+            finalize: Arc::new(move |state| finalize(*state.downcast().map_err(|_| mismatch())?)),
+        }
+    }
+}
+
+pub struct Aggregate<T: Transaction> {
+    source: Box<dyn Executor<T>>,
+    group_by: Vec<String>,
+    aggregates: Vec<Expression>,
+}
+
+impl<T: Transaction> Aggregate<T> {
+    pub fn new(
+        source: Box<dyn Executor<T>>,
+        group_by: Vec<String>,
+        aggregates: Vec<Expression>,
+    ) -> Box<Self> {
+        Box::new(Self {
+            source,
+            group_by,
+            aggregates,
+        })
+    }
+}
+
+impl<T: Transaction> Executor<T> for Aggregate<T> {
+    fn execute(self: Box<Self>, txn: &mut T, ctx: &ExecContext) -> Result<ResultSet> {
+        match self.source.execute(txn, ctx)? {
+            ResultSet::Scan {
+                columns,
+                rows,
+                skipped,
+            } => {
+                let accumulators = || {
+                    self.aggregates
+                        .iter()
+                        .map(|expr| accumulator(expr, &ctx.aggregates))
+                        .collect::<Result<Vec<_>>>()
+                };
+                // The groups in the order they are first seen, and their index by the
+                // grouped column values. Without grouped columns there is always the one
+                // group.
+                let mut groups = Vec::new();
+                let mut indexes = HashMap::new();
+                if self.group_by.is_empty() {
+                    groups.push((Vec::new(), accumulators()?));
+                    indexes.insert(Vec::new(), 0);
+                }
+                for row in rows.iter() {
+                    let key = self
+                        .group_by
+                        .iter()
+                        .map(|column| Ok(row.by_name(&columns, column)?.clone()))
+                        .collect::<Result<Vec<_>>>()?;
+                    let index = match indexes.get(&key) {
+                        Some(index) => *index,
+                        None => {
+                            indexes.insert(key.clone(), groups.len());
+                            groups.push((key, accumulators()?));
+                            groups.len() - 1
+                        }
+                    };
+                    let group = &mut groups[index].1;
+                    for (accumulator, expr) in group.iter_mut().zip(&self.aggregates) {
+                        let args = match expr {
+                            Expression::Function { args, .. } => args
+                                .iter()
+                                .map(|arg| evaluate(arg, &columns, row, ctx))
+                                .collect::<Result<Vec<_>>>()?,
+                            _ => Vec::new(),
+                        };
+                        accumulator.accumulate(&args)?;
+                    }
+                }
+                let rows = groups
+                    .into_iter()
+                    .map(|(mut row, accumulators)| {
+                        for accumulator in accumulators {
+                            row.push(accumulator.finalize()?);
+                        }
+                        Ok(row)
+                    })
+                    .collect::<Result<_>>()?;
+                let mut columns = self.group_by.clone();
+                columns.extend(self.aggregates.iter().map(Expression::column_name));
+                Ok(ResultSet::Scan {
+                    columns,
+                    rows,
+                    skipped,
+                })
+            }
+            r => Err(Error::Internal(format!("unexpected result set {:?}", r))),
+        }
+    }
+}
+
+// Folds the argument values of an aggregate over the rows.
+trait Accumulator {
+    fn accumulate(&mut self, args: &[Value]) -> Result<()>;
+    fn finalize(self: Box<Self>) -> Result<Value>;
+}
+
+// Built-in aggregates take precedence over custom ones of the same name, which are looked
+// up in the session's registry.
+fn accumulator(
+    expr: &Expression,
+    custom: &HashMap<String, CustomAggregate>,
+) -> Result<Box<dyn Accumulator>> {
+    let (name, args) = match expr {
+        Expression::Function { name, args } => (name, args),
+        expr => {
+            return Err(Error::Internal(format!(
+                "expression {:?} is not an aggregate",
+                expr
+            )))
+        }
+    };
+    match (name.as_str(), args.len()) {
+        ("COUNT", 0 | 1) => Ok(Box::new(Count(0))),
+        ("SUM", 1) => Ok(Box::new(Sum::default())),
+        ("AVG", 1) => Ok(Box::new(Avg(Sum::default()))),
+        ("MIN", 1) => Ok(Box::new(Extreme::new(Ordering::Less))),
+        ("MAX", 1) => Ok(Box::new(Extreme::new(Ordering::Greater))),
+        (name, _) if custom.contains_key(name) => {
+            let aggregate = custom[name].clone();
+            let state = (aggregate.init)();
+            Ok(Box::new(Custom { aggregate, state }))
+        }
+        _ => Err(Error::Parse(format!(
+            "unknown aggregate function {}",
+            expr.to_sql()
+        ))),
+    }
+}
+
+// COUNT(*) counts the rows, COUNT(expr) the rows where expr is not NULL.
+struct Count(i64);
+
+impl Accumulator for Count {
+    fn accumulate(&mut self, args: &[Value]) -> Result<()> {
+        if args.iter().all(|arg| *arg != Value::Null) {
+            self.0 += 1;
+        }
+        Ok(())
+    }
+
+    fn finalize(self: Box<Self>) -> Result<Value> {
+        Ok(Value::Integer(self.0))
+    }
+}
+
+// The sum of the non-NULL numeric values, NULL without any.
+#[derive(Default)]
+struct Sum {
+    sum: Option<Value>,
+    count: i64,
+}
+
+impl Accumulator for Sum {
+    fn accumulate(&mut self, args: &[Value]) -> Result<()> {
+        let value = &args[0];
+        if *value == Value::Null {
+            return Ok(());
+        }
+        if value.to_f64().is_none() {
+            return Err(Error::Internal(format!(
+                "cannot aggregate non-numeric value {:?}",
+                value
+            )));
+        }
+        self.sum = Some(match self.sum.take() {
+            Some(sum) => sum.add(value)?,
+            None => value.clone(),
+        });
+        self.count += 1;
+        Ok(())
+    }
+
+    fn finalize(self: Box<Self>) -> Result<Value> {
+        Ok(self.sum.unwrap_or(Value::Null))
+    }
+}
+
+// The mean of the non-NULL numeric values as a float, NULL without any.
+struct Avg(Sum);
+
+impl Accumulator for Avg {
+    fn accumulate(&mut self, args: &[Value]) -> Result<()> {
+        self.0.accumulate(args)
+    }
+
+    fn finalize(self: Box<Self>) -> Result<Value> {
+        Ok(match self.0.sum.as_ref().and_then(Value::to_f64) {
+            Some(sum) => Value::Float(sum / self.0.count as f64),
+            None => Value::Null,
+        })
+    }
+}
+
+// MIN or MAX of the non-NULL values, keeping a value that compares as the given ordering
+// to the one kept so far. NULL without any.
+struct Extreme {
+    ordering: Ordering,
+    value: Option<Value>,
+}
+
+impl Extreme {
+    fn new(ordering: Ordering) -> Self {
+        Self {
+            ordering,
+            value: None,
+        }
+    }
+}
+
+impl Accumulator for Extreme {
+    fn accumulate(&mut self, args: &[Value]) -> Result<()> {
+        let value = &args[0];
+        if *value == Value::Null {
+            return Ok(());
+        }
+        let replace = match &self.value {
+            Some(current) => value.compare(current)? == self.ordering,
+            None => true,
+        };
+        if replace {
+            self.value = Some(value.clone());
+        }
+        Ok(())
+    }
+
+    fn finalize(self: Box<Self>) -> Result<Value> {
+        Ok(self.value.unwrap_or(Value::Null))
+    }
+}
+
+struct Custom {
+    aggregate: CustomAggregate,
+    state: State,
+}
+
+impl Accumulator for Custom {
+    fn accumulate(&mut self, args: &[Value]) -> Result<()> {
+        (self.aggregate.accumulate)(&mut self.state, args)
+    }
+
+    fn finalize(self: Box<Self>) -> Result<Value> {
+        (self.aggregate.finalize)(self.state)
+    }
+}
@@ -1,31 +1,178 @@
-use mutation::Insert;
-use query::Scan;
+pub use aggregate::CustomAggregate;
+
+use aggregate::Aggregate;
+use mutation::{Delete, Insert, Update};
+use query::{Filter, Limit, Lock, Offset, Order, Projection, Scan, ValuesScan};
 use schema::CreateTable;
 
-use crate::error::Result;
-use super::{engine::Transaction, plan::Node, types::Row};
+use std::{collections::HashMap, fmt::Display, sync::Arc};
+
+use serde::{Deserialize, Serialize};
 
+use crate::error::{Error, Result};
+use super::{
+    engine::Transaction,
+    parser::ast::Expression,
+    plan::Node,
+    types::{IntegerDivision, Row, RowExt, Value},
+};
+
+mod aggregate;
 mod schema;
 mod mutation;
 mod query;
 
+// Settings of the session which change how statements evaluate.
+#[derive(Clone, Default)]
+pub struct ExecContext {
+    pub integer_division: IntegerDivision,
+    // The custom aggregates by their uppercase name, shared with the session.
+    pub aggregates: Arc<HashMap<String, CustomAggregate>>,
+}
+
 pub trait Executor<T: Transaction> {
-    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet>;
+    fn execute(self: Box<Self>, txn: &mut T, ctx: &ExecContext) -> Result<ResultSet>;
 }
 
-impl<T: Transaction> dyn Executor<T> {
+impl<T: Transaction + 'static> dyn Executor<T> {
     pub fn build(node: Node) -> Box<dyn Executor<T>> {
         match node {
             Node::CreateTable { schema } => CreateTable::new(schema),
-            Node::Insert { table_name, columns, values } => Insert::new(table_name, columns, values),
-            Node::Scan { table_name } => Scan::new(table_name),
+            Node::Insert {
+                table_name,
+                columns,
+                values,
+                replace,
+                collect_errors,
+            } => Insert::new(table_name, columns, values, replace, collect_errors),
+            Node::Scan {
+                table_name,
+                filter,
+                lenient,
+            } => Scan::new(table_name, filter, lenient),
+            Node::ValuesScan { exprs } => ValuesScan::new(exprs),
+            Node::Filter { source, predicate } => Filter::new(Self::build(*source), predicate),
+            Node::Lock { table_name, source } => Lock::new(table_name, Self::build(*source)),
+            Node::Order { source, order_by } => Order::new(Self::build(*source), order_by),
+            Node::Offset { source, offset } => Offset::new(Self::build(*source), offset),
+            Node::Limit { source, limit } => Limit::new(Self::build(*source), limit),
+            Node::Aggregate {
+                source,
+                group_by,
+                aggregates,
+            } => Aggregate::new(Self::build(*source), group_by, aggregates),
+            Node::Projection { source, exprs } => Projection::new(Self::build(*source), exprs),
+            Node::Delete { table_name, source } => Delete::new(table_name, Self::build(*source)),
+            Node::Update {
+                table_name,
+                source,
+                assignments,
+            } => Update::new(table_name, Self::build(*source), assignments),
         }
     }
 }
 
-#[derive(Debug)]
+// Evaluates the expression for a row, looking fields up by the row's column names.
+fn evaluate(expr: &Expression, columns: &[String], row: &Row, ctx: &ExecContext) -> Result<Value> {
+    Ok(match expr {
+        Expression::Consts(_) => Value::from_expression(expr.clone())?,
+        Expression::Field(name) => row.by_name(columns, name)?.clone(),
+        Expression::Operation(operation) => {
+            Value::evaluate_operation(operation, ctx.integer_division, &|e| {
+                evaluate(e, columns, row, ctx)
+            })?
+        }
+        // Aggregates are computed by the Aggregate node into columns named by their SQL.
+        Expression::Function { .. } => row.by_name(columns, &expr.to_sql())?.clone(),
+        // Subqueries are resolved by the Filter node before evaluating the predicate.
+        Expression::Subquery { .. } => {
+            return Err(Error::Internal(format!(
+                "subquery {} is only supported in WHERE",
+                expr.to_sql()
+            )))
+        }
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub enum ResultSet {
-    CrateTable { table_name: String },
-    Insert { count: usize },
-    Scan { columns: Vec<String>, rows: Vec<Row> },
+    CrateTable {
+        table_name: String,
+    },
+    // Errors of the skipped rows by their index in the VALUES list, only ever non-empty
+    // when the insert was planned to collect errors.
+    Insert {
+        count: usize,
+        errors: Vec<(usize, Error)>,
+    },
+    Delete {
+        count: usize,
+    },
+    Update {
+        count: usize,
+    },
+    // Primary keys of the rows a lenient scan skipped because they couldn't be read.
+    Scan {
+        columns: Vec<String>,
+        rows: Vec<Row>,
+        skipped: Vec<Value>,
+    },
+    Begin {
+        version: u64,
+    },
+    Commit {
+        version: u64,
+    },
+    Rollback {
+        version: u64,
+    },
+}
+
+impl Display for ResultSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResultSet::CrateTable { table_name } => write!(f, "Table {} created", table_name),
+            ResultSet::Insert { count, .. } => write!(f, "{} rows inserted", count),
+            ResultSet::Delete { count } => write!(f, "{} rows deleted", count),
+            ResultSet::Update { count } => write!(f, "{} rows updated", count),
+            ResultSet::Begin { version } => write!(f, "Transaction {} started", version),
+            ResultSet::Commit { version } => write!(f, "Transaction {} committed", version),
+            ResultSet::Rollback { version } => write!(f, "Transaction {} rolled back", version),
+            ResultSet::Scan { columns, rows, .. } => {
+                // Every column is as wide as its widest value or header.
+                let rows = rows
+                    .iter()
+                    .map(|row| row.iter().map(|v| v.to_string()).collect::<Vec<_>>())
+                    .collect::<Vec<_>>();
+                let widths = columns
+                    .iter()
+                    .enumerate()
+                    .map(|(i, c)| {
+                        rows.iter()
+                            .filter_map(|row| row.get(i))
+                            .map(|v| v.chars().count())
+                            .fold(c.chars().count(), usize::max)
+                    })
+                    .collect::<Vec<_>>();
+
+                let line = |values: Vec<&str>| {
+                    values
+                        .iter()
+                        .zip(&widths)
+                        .map(|(v, width)| format!("{:<width$}", v, width = width))
+                        .collect::<Vec<_>>()
+                        .join(" | ")
+                        .trim_end()
+                        .to_string()
+                };
+                writeln!(f, "{}", line(columns.iter().map(|c| c.as_str()).collect()))?;
+                let separator = widths.iter().map(|w| "-".repeat(*w)).collect::<Vec<_>>();
+                write!(f, "{}", separator.join("-+-"))?;
+                for row in &rows {
+                    write!(f, "\n{}", line(row.iter().map(|v| v.as_str()).collect()))?;
+                }
+                Ok(())
+            }
+        }
+    }
 }
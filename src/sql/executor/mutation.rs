@@ -6,16 +6,18 @@ use crate::{
         engine::Transaction,
         parser::ast::Expression,
         schema::Table,
-        types::{Row, Value},
+        types::Row,
     },
 };
 
-use super::{Executor, ResultSet};
+use super::{evaluate, ExecContext, Executor, ResultSet};
 
 pub struct Insert {
     table_name: String,
     columns: Vec<String>,
     values: Vec<Vec<Expression>>,
+    replace: bool,
+    collect_errors: bool,
 }
 
 impl Insert {
@@ -23,13 +25,42 @@ impl Insert {
         table_name: String,
         columns: Vec<String>,
         values: Vec<Vec<Expression>>,
+        replace: bool,
+        collect_errors: bool,
     ) -> Box<Self> {
         Box::new(Self {
             table_name,
             columns,
             values,
+            replace,
+            collect_errors,
         })
     }
+
+    fn insert_row<T: Transaction>(
+        &self,
+        txn: &mut T,
+        table: &Table,
+        exprs: Vec<Expression>,
+        ctx: &ExecContext,
+    ) -> Result<()> {
+        let row = exprs
+            .iter()
+            .map(|expr| evaluate(expr, &[], &Vec::new(), ctx))
+            .collect::<Result<Vec<_>>>()?;
+        let insert_row = table.coerce_row(if self.columns.is_empty() {
+            pad_row(table, &row)
+        } else {
+            make_row(table, &self.columns, &row)
+        }?);
+
+        if self.replace {
+            // Validate first so that a skipped row does not delete the one it replaces.
+            table.validate_row(&insert_row)?;
+            txn.delete_row(self.table_name.clone(), table.get_row_key(&insert_row)?)?;
+        }
+        txn.create_row(self.table_name.clone(), insert_row)
+    }
 }
 
 fn pad_row(table: &Table, row: &Row) -> Result<Row> {
@@ -76,24 +107,99 @@ fn make_row(table: &Table, columns: &Vec<String>, values: &Row) -> Result<Row> {
 }
 
 impl<T: Transaction> Executor<T> for Insert {
-    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+    fn execute(mut self: Box<Self>, txn: &mut T, ctx: &ExecContext) -> Result<ResultSet> {
         let mut count = 0;
+        let mut errors = Vec::new();
         let table = txn.must_get_table(self.table_name.clone())?;
-        for exprs in self.values {
-            let row = exprs
-                .into_iter()
-                .map(|e| Value::from_expression(e))
-                .collect::<Vec<_>>();
-            let insert_row = if self.columns.is_empty() {
-                pad_row(&table, &row)
-            } else {
-                make_row(&table, &self.columns, &row)
-            }?;
-
-            txn.create_row(self.table_name.clone(), insert_row)?;
-            count += 1;
+        for (i, exprs) in std::mem::take(&mut self.values).into_iter().enumerate() {
+            match self.insert_row(txn, &table, exprs, ctx) {
+                Ok(()) => count += 1,
+                Err(err) if self.collect_errors => errors.push((i, err)),
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(ResultSet::Insert { count, errors })
+    }
+}
+
+pub struct Delete<T: Transaction> {
+    table_name: String,
+    source: Box<dyn Executor<T>>,
+}
+
+impl<T: Transaction> Delete<T> {
+    pub fn new(table_name: String, source: Box<dyn Executor<T>>) -> Box<Self> {
+        Box::new(Self { table_name, source })
+    }
+}
+
+impl<T: Transaction> Executor<T> for Delete<T> {
+    fn execute(self: Box<Self>, txn: &mut T, ctx: &ExecContext) -> Result<ResultSet> {
+        match self.source.execute(txn, ctx)? {
+            ResultSet::Scan { rows, .. } => {
+                let table = txn.must_get_table(self.table_name.clone())?;
+                let count = rows.len();
+                for row in rows {
+                    txn.delete_row(self.table_name.clone(), table.get_row_key(&row)?)?;
+                }
+                Ok(ResultSet::Delete { count })
+            }
+            r => Err(Error::Internal(format!("unexpected result set {:?}", r))),
         }
+    }
+}
 
-        Ok(ResultSet::Insert { count })
+pub struct Update<T: Transaction> {
+    table_name: String,
+    source: Box<dyn Executor<T>>,
+    assignments: Vec<(String, Expression)>,
+}
+
+impl<T: Transaction> Update<T> {
+    pub fn new(
+        table_name: String,
+        source: Box<dyn Executor<T>>,
+        assignments: Vec<(String, Expression)>,
+    ) -> Box<Self> {
+        Box::new(Self {
+            table_name,
+            source,
+            assignments,
+        })
+    }
+}
+
+impl<T: Transaction> Executor<T> for Update<T> {
+    fn execute(self: Box<Self>, txn: &mut T, ctx: &ExecContext) -> Result<ResultSet> {
+        let table = txn.must_get_table(self.table_name.clone())?;
+        let mut assignments = Vec::new();
+        for (name, expr) in &self.assignments {
+            match table.columns.iter().position(|c| &c.name == name) {
+                Some(idx) => assignments.push((idx, expr)),
+                None => return Err(Error::Internal(format!("column {} does not exist", name))),
+            }
+        }
+
+        match self.source.execute(txn, ctx)? {
+            ResultSet::Scan { columns, rows, .. } => {
+                let count = rows.len();
+                for row in rows {
+                    // Assignments are evaluated against the row as it was before the update.
+                    let mut new_row = row.clone();
+                    for (idx, expr) in &assignments {
+                        new_row[*idx] = evaluate(expr, &columns, &row, ctx)?;
+                    }
+                    let new_row = table.coerce_row(new_row);
+                    table.validate_row(&new_row)?;
+
+                    // The row is written anew, possibly under a changed primary key.
+                    txn.delete_row(self.table_name.clone(), table.get_row_key(&row)?)?;
+                    txn.create_row(self.table_name.clone(), new_row)?;
+                }
+                Ok(ResultSet::Update { count })
+            }
+            r => Err(Error::Internal(format!("unexpected result set {:?}", r))),
+        }
     }
 }
@@ -1,23 +1,431 @@
-use super::{Executor, ResultSet};
-use crate::{error::Result, sql::engine::Transaction};
+use std::cmp::Ordering;
+
+use super::{evaluate, ExecContext, Executor, ResultSet};
+use crate::{
+    error::{Error, Result},
+    sql::{
+        engine::Transaction,
+        parser::ast::{Consts, Expression, Operation, OrderDirection, Quantifier},
+        plan::Plan,
+        types::Value,
+    },
+};
 
 pub struct Scan {
     table_name: String,
+    filter: Option<(String, Expression)>,
+    lenient: bool,
 }
 
 impl Scan {
-    pub fn new(table_name: String) -> Box<Self> {
-        Box::new(Self { table_name })
+    pub fn new(
+        table_name: String,
+        filter: Option<(String, Expression)>,
+        lenient: bool,
+    ) -> Box<Self> {
+        Box::new(Self {
+            table_name,
+            filter,
+            lenient,
+        })
     }
 }
 
 impl<T: Transaction> Executor<T> for Scan {
-    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+    fn execute(self: Box<Self>, txn: &mut T, ctx: &ExecContext) -> Result<ResultSet> {
         let table = txn.must_get_table(self.table_name.clone())?;
-        let rows = txn.scan_table(self.table_name.clone())?;
+        let (rows, skipped) = match self.filter {
+            Some((column, value)) => match table.columns.iter().position(|c| c.name == column) {
+                Some(col_index) => {
+                    let value = evaluate(&value, &[], &Vec::new(), ctx)?;
+                    let rows = txn.scan_table_where(self.table_name.clone(), col_index, &value)?;
+                    (rows, Vec::new())
+                }
+                // As with a filter, an unknown column only errors once there is a row.
+                None => {
+                    let rows = txn.scan_table(self.table_name.clone())?;
+                    if !rows.is_empty() {
+                        return Err(Error::Internal(format!("column {} does not exist", column)));
+                    }
+                    (rows, Vec::new())
+                }
+            },
+            None if self.lenient => txn.scan_table_lenient(self.table_name.clone())?,
+            None => (txn.scan_table(self.table_name.clone())?, Vec::new()),
+        };
+        if rows.iter().any(|row| row.len() != table.columns.len()) {
+            return Err(Error::Internal("row/schema arity mismatch".to_string()));
+        }
         Ok(ResultSet::Scan {
             columns: table.columns.into_iter().map(|c| c.name.clone()).collect(),
             rows,
+            skipped,
         })
     }
 }
+
+pub struct ValuesScan {
+    exprs: Vec<Expression>,
+}
+
+impl ValuesScan {
+    pub fn new(exprs: Vec<Expression>) -> Box<Self> {
+        Box::new(Self { exprs })
+    }
+}
+
+impl<T: Transaction> Executor<T> for ValuesScan {
+    fn execute(self: Box<Self>, _txn: &mut T, ctx: &ExecContext) -> Result<ResultSet> {
+        // There is no row to take fields from, so only constant expressions evaluate.
+        let row = self
+            .exprs
+            .iter()
+            .map(|expr| evaluate(expr, &[], &Vec::new(), ctx))
+            .collect::<Result<_>>()?;
+        Ok(ResultSet::Scan {
+            columns: self.exprs.iter().map(Expression::column_name).collect(),
+            rows: vec![row],
+            skipped: Vec::new(),
+        })
+    }
+}
+
+pub struct Filter<T: Transaction> {
+    source: Box<dyn Executor<T>>,
+    predicate: Expression,
+}
+
+impl<T: Transaction> Filter<T> {
+    pub fn new(source: Box<dyn Executor<T>>, predicate: Expression) -> Box<Self> {
+        Box::new(Self { source, predicate })
+    }
+}
+
+// Replaces each comparison with an ANY or ALL subquery by the comparisons with every value
+// of the subquery, joined by OR for ANY and by AND for ALL. This gives the SQL semantics,
+// including for NULL values, while an empty subquery makes ANY false and ALL true.
+fn resolve_subqueries<T: Transaction + 'static>(
+    expr: &mut Expression,
+    txn: &mut T,
+    ctx: &ExecContext,
+) -> Result<()> {
+    let operation = match expr {
+        Expression::Operation(operation) => operation,
+        _ => return Ok(()),
+    };
+    // The subqueries may be nested in any operand, e.g. (a = ANY (...)) = TRUE.
+    for operand in operation.operands_mut() {
+        resolve_subqueries(operand, txn, ctx)?;
+    }
+
+    type Comparison = fn(Box<Expression>, Box<Expression>) -> Operation;
+    let (left, right, comparison): (_, _, Comparison) = match operation {
+        Operation::Equal(l, r) => (l, r, Operation::Equal),
+        Operation::NotEqual(l, r) => (l, r, Operation::NotEqual),
+        Operation::LessThan(l, r) => (l, r, Operation::LessThan),
+        Operation::LessThanOrEqual(l, r) => (l, r, Operation::LessThanOrEqual),
+        Operation::GreaterThan(l, r) => (l, r, Operation::GreaterThan),
+        Operation::GreaterThanOrEqual(l, r) => (l, r, Operation::GreaterThanOrEqual),
+        _ => return Ok(()),
+    };
+    let (quantifier, query) = match right.as_ref() {
+        Expression::Subquery { quantifier, query } => (*quantifier, query.as_ref().clone()),
+        _ => return Ok(()),
+    };
+
+    let values = match Plan::build(query)?.execute(txn, ctx)? {
+        ResultSet::Scan { columns, rows, .. } if columns.len() == 1 => {
+            rows.into_iter().map(|mut row| row.remove(0))
+        }
+        ResultSet::Scan { columns, .. } => {
+            return Err(Error::Internal(format!(
+                "subquery returns {} columns instead of one",
+                columns.len()
+            )))
+        }
+        r => return Err(Error::Internal(format!("unexpected result set {:?}", r))),
+    };
+    let comparisons = values
+        .map(|value| {
+            let value = Box::new(Expression::Consts(Consts::from(value)));
+            Expression::Operation(comparison(left.clone(), value))
+        })
+        .collect::<Vec<_>>();
+    *expr = match quantifier {
+        Quantifier::Any => join(comparisons, Operation::Or, false),
+        Quantifier::All => join(comparisons, Operation::And, true),
+    };
+    Ok(())
+}
+
+// Joins the expressions into a balanced tree, so that evaluating it doesn't recurse deeply
+// for large subqueries.
+fn join(
+    mut exprs: Vec<Expression>,
+    operation: fn(Box<Expression>, Box<Expression>) -> Operation,
+    empty: bool,
+) -> Expression {
+    match exprs.len() {
+        0 => Expression::Consts(Consts::Boolean(empty)),
+        1 => exprs.remove(0),
+        len => {
+            let right = exprs.split_off(len / 2);
+            let (l, r) = (join(exprs, operation, empty), join(right, operation, empty));
+            Expression::Operation(operation(Box::new(l), Box::new(r)))
+        }
+    }
+}
+
+impl<T: Transaction + 'static> Executor<T> for Filter<T> {
+    fn execute(self: Box<Self>, txn: &mut T, ctx: &ExecContext) -> Result<ResultSet> {
+        let mut predicate = self.predicate;
+        resolve_subqueries(&mut predicate, txn, ctx)?;
+        match self.source.execute(txn, ctx)? {
+            ResultSet::Scan {
+                columns,
+                rows,
+                skipped,
+            } => {
+                let mut results = Vec::new();
+                for row in rows {
+                    // Rows for which the predicate is false or NULL are dropped.
+                    match evaluate(&predicate, &columns, &row, ctx)? {
+                        Value::Boolean(true) => results.push(row),
+                        Value::Boolean(false) | Value::Null => {}
+                        v => {
+                            return Err(Error::Internal(format!(
+                                "predicate returned non-boolean value {:?}",
+                                v
+                            )))
+                        }
+                    }
+                }
+                Ok(ResultSet::Scan {
+                    columns,
+                    rows: results,
+                    skipped,
+                })
+            }
+            r => Err(Error::Internal(format!("unexpected result set {:?}", r))),
+        }
+    }
+}
+
+pub struct Lock<T: Transaction> {
+    table_name: String,
+    source: Box<dyn Executor<T>>,
+}
+
+impl<T: Transaction> Lock<T> {
+    pub fn new(table_name: String, source: Box<dyn Executor<T>>) -> Box<Self> {
+        Box::new(Self { table_name, source })
+    }
+}
+
+impl<T: Transaction> Executor<T> for Lock<T> {
+    fn execute(self: Box<Self>, txn: &mut T, ctx: &ExecContext) -> Result<ResultSet> {
+        match self.source.execute(txn, ctx)? {
+            ResultSet::Scan {
+                columns,
+                rows,
+                skipped,
+            } => {
+                let table = txn.must_get_table(self.table_name.clone())?;
+                for row in rows.iter() {
+                    txn.lock_row(self.table_name.clone(), table.get_row_key(row)?)?;
+                }
+                Ok(ResultSet::Scan {
+                    columns,
+                    rows,
+                    skipped,
+                })
+            }
+            r => Err(Error::Internal(format!("unexpected result set {:?}", r))),
+        }
+    }
+}
+
+pub struct Order<T: Transaction> {
+    source: Box<dyn Executor<T>>,
+    order_by: Vec<(String, OrderDirection)>,
+}
+
+impl<T: Transaction> Order<T> {
+    pub fn new(source: Box<dyn Executor<T>>, order_by: Vec<(String, OrderDirection)>) -> Box<Self> {
+        Box::new(Self { source, order_by })
+    }
+}
+
+impl<T: Transaction> Executor<T> for Order<T> {
+    fn execute(self: Box<Self>, txn: &mut T, ctx: &ExecContext) -> Result<ResultSet> {
+        match self.source.execute(txn, ctx)? {
+            ResultSet::Scan {
+                columns,
+                mut rows,
+                skipped,
+            } => {
+                let order_by = self
+                    .order_by
+                    .iter()
+                    .map(|(name, direction)| {
+                        let idx = columns
+                            .iter()
+                            .position(|c| c == name)
+                            .ok_or(Error::Parse(format!("column {} does not exist", name)))?;
+                        Ok((idx, direction))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+
+                // NULLs sort first. Numbers of any type compare with each other, any other
+                // mix of types is an error, which sort_by can only hand out afterwards.
+                // The sort is stable: rows with equal keys keep the order of the source,
+                // which for a table scan is the order of the stored keys, so ties always
+                // come out the same way.
+                let numeric =
+                    |v: &Value| matches!(v, Value::Integer(_) | Value::BigInt(_) | Value::Float(_));
+                let mut error = None;
+                rows.sort_by(|a, b| {
+                    for (idx, direction) in order_by.iter() {
+                        let (l, r) = (&a[*idx], &b[*idx]);
+                        if *l != Value::Null && *r != Value::Null {
+                            if let Err(err) = l.compare(r) {
+                                if !(numeric(l) && numeric(r)) {
+                                    error.get_or_insert(err);
+                                }
+                            }
+                        }
+                        let ordering = match direction {
+                            OrderDirection::Asc => l.sort_cmp(r),
+                            OrderDirection::Desc => r.sort_cmp(l),
+                        };
+                        if ordering != Ordering::Equal {
+                            return ordering;
+                        }
+                    }
+                    Ordering::Equal
+                });
+                if let Some(err) = error {
+                    return Err(err);
+                }
+                Ok(ResultSet::Scan {
+                    columns,
+                    rows,
+                    skipped,
+                })
+            }
+            r => Err(Error::Internal(format!("unexpected result set {:?}", r))),
+        }
+    }
+}
+
+pub struct Offset<T: Transaction> {
+    source: Box<dyn Executor<T>>,
+    offset: usize,
+}
+
+impl<T: Transaction> Offset<T> {
+    pub fn new(source: Box<dyn Executor<T>>, offset: usize) -> Box<Self> {
+        Box::new(Self { source, offset })
+    }
+}
+
+impl<T: Transaction> Executor<T> for Offset<T> {
+    fn execute(self: Box<Self>, txn: &mut T, ctx: &ExecContext) -> Result<ResultSet> {
+        match self.source.execute(txn, ctx)? {
+            ResultSet::Scan {
+                columns,
+                rows,
+                skipped,
+            } => Ok(ResultSet::Scan {
+                columns,
+                rows: rows.into_iter().skip(self.offset).collect(),
+                skipped,
+            }),
+            r => Err(Error::Internal(format!("unexpected result set {:?}", r))),
+        }
+    }
+}
+
+pub struct Limit<T: Transaction> {
+    source: Box<dyn Executor<T>>,
+    limit: usize,
+}
+
+impl<T: Transaction> Limit<T> {
+    pub fn new(source: Box<dyn Executor<T>>, limit: usize) -> Box<Self> {
+        Box::new(Self { source, limit })
+    }
+}
+
+impl<T: Transaction> Executor<T> for Limit<T> {
+    fn execute(self: Box<Self>, txn: &mut T, ctx: &ExecContext) -> Result<ResultSet> {
+        match self.source.execute(txn, ctx)? {
+            ResultSet::Scan {
+                columns,
+                rows,
+                skipped,
+            } => Ok(ResultSet::Scan {
+                columns,
+                rows: rows.into_iter().take(self.limit).collect(),
+                skipped,
+            }),
+            r => Err(Error::Internal(format!("unexpected result set {:?}", r))),
+        }
+    }
+}
+
+pub struct Projection<T: Transaction> {
+    source: Box<dyn Executor<T>>,
+    exprs: Vec<Expression>,
+}
+
+impl<T: Transaction> Projection<T> {
+    pub fn new(source: Box<dyn Executor<T>>, exprs: Vec<Expression>) -> Box<Self> {
+        Box::new(Self { source, exprs })
+    }
+}
+
+// Fails for a field which is not a source column, also when there are no rows.
+fn check_fields(expr: &Expression, columns: &[String]) -> Result<()> {
+    match expr {
+        Expression::Field(name) if !columns.contains(name) => {
+            Err(Error::Parse(format!("column {} does not exist", name)))
+        }
+        Expression::Operation(operation) => operation
+            .operands()
+            .into_iter()
+            .try_for_each(|expr| check_fields(expr, columns)),
+        _ => Ok(()),
+    }
+}
+
+impl<T: Transaction> Executor<T> for Projection<T> {
+    fn execute(self: Box<Self>, txn: &mut T, ctx: &ExecContext) -> Result<ResultSet> {
+        match self.source.execute(txn, ctx)? {
+            ResultSet::Scan {
+                columns,
+                rows,
+                skipped,
+            } => {
+                for expr in self.exprs.iter() {
+                    check_fields(expr, &columns)?;
+                }
+                let rows = rows
+                    .iter()
+                    .map(|row| {
+                        self.exprs
+                            .iter()
+                            .map(|expr| evaluate(expr, &columns, row, ctx))
+                            .collect()
+                    })
+                    .collect::<Result<_>>()?;
+                Ok(ResultSet::Scan {
+                    columns: self.exprs.iter().map(Expression::column_name).collect(),
+                    rows,
+                    skipped,
+                })
+            }
+            r => Err(Error::Internal(format!("unexpected result set {:?}", r))),
+        }
+    }
+}
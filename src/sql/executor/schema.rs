@@ -1,4 +1,4 @@
-use super::{Executor, ResultSet};
+use super::{ExecContext, Executor, ResultSet};
 use crate::{error::Result, sql::{engine::Transaction, schema::Table}};
 
 pub struct CreateTable {
@@ -12,7 +12,7 @@ impl CreateTable {
 }
 
 impl<T: Transaction> Executor<T> for CreateTable {
-    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+    fn execute(self: Box<Self>, txn: &mut T, _ctx: &ExecContext) -> Result<ResultSet> {
         let table_name = self.schema.name.clone();
         txn.create_table(self.schema)?;
         Ok(ResultSet::CrateTable { table_name })
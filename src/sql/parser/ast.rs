@@ -1,29 +1,264 @@
-use crate::sql::types::DataType;
+use super::Keyword;
+use crate::sql::types::{DataType, Value};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Statement {
-    CreateTable { name: String, columns: Vec<Column> },
+    CreateTable {
+        name: String,
+        columns: Vec<Column>,
+        temporary: bool,
+    },
     Insert {
         table_name: String,
         columns: Option<Vec<String>>,
-        values: Vec<Vec<Expression>>
+        values: Vec<Vec<Expression>>,
+        replace: bool,
     },
     Select {
-        table_name: String
+        // None without FROM, the columns are then evaluated once as constants.
+        table_name: Option<String>,
+        // None for `*`.
+        columns: Option<Vec<Expression>>,
+        filter: Option<Expression>,
+        group_by: Vec<String>,
+        order_by: Vec<(String, OrderDirection)>,
+        limit: Option<usize>,
+        offset: Option<usize>,
+        // Locks the matched rows against concurrent writes until the transaction ends.
+        for_update: bool,
+    },
+    Delete {
+        table_name: String,
+        filter: Option<Expression>,
+    },
+    Update {
+        table_name: String,
+        assignments: Vec<(String, Expression)>,
+        filter: Option<Expression>,
+    },
+    Begin,
+    Commit,
+    Rollback,
+}
+
+impl Statement {
+    // None for the statements which don't access a table.
+    pub fn table_name(&self) -> Option<&str> {
+        match self {
+            Statement::CreateTable { name, .. } => Some(name),
+            Statement::Insert { table_name, .. } => Some(table_name),
+            Statement::Select { table_name, .. } => table_name.as_deref(),
+            Statement::Delete { table_name, .. } => Some(table_name),
+            Statement::Update { table_name, .. } => Some(table_name),
+            Statement::Begin | Statement::Commit | Statement::Rollback => None,
+        }
+    }
+
+    // Renders the statement as SQL which parses back into an equal statement.
+    pub fn to_sql(&self) -> String {
+        let mut sql = match self {
+            Statement::CreateTable {
+                name,
+                columns,
+                temporary,
+            } => format!(
+                "CREATE {}TABLE {} ({})",
+                if *temporary { "TEMPORARY " } else { "" },
+                quote_ident(name),
+                join(columns.iter().map(Column::to_sql)),
+            ),
+            Statement::Insert {
+                table_name,
+                columns,
+                values,
+                replace,
+            } => {
+                let verb = if *replace { "REPLACE" } else { "INSERT" };
+                let mut sql = format!("{} INTO {}", verb, quote_ident(table_name));
+                if let Some(columns) = columns {
+                    sql += &format!(" ({})", join(columns.iter().map(|c| quote_ident(c))));
+                }
+                if columns.is_none() && values.iter().all(|row| row.is_empty()) {
+                    sql += " DEFAULT VALUES";
+                } else {
+                    let rows = values
+                        .iter()
+                        .map(|row| format!("({})", join(row.iter().map(Expression::to_sql))));
+                    sql += &format!(" VALUES {}", join(rows));
+                }
+                sql
+            }
+            Statement::Select {
+                table_name,
+                columns,
+                filter,
+                group_by,
+                order_by,
+                limit,
+                offset,
+                for_update,
+            } => {
+                let mut sql = match columns {
+                    Some(columns) => {
+                        format!("SELECT {}", join(columns.iter().map(Expression::to_sql)))
+                    }
+                    None => "SELECT *".to_string(),
+                };
+                if let Some(table_name) = table_name {
+                    sql += &format!(" FROM {}", quote_ident(table_name));
+                }
+                sql += &where_sql(filter);
+                if !group_by.is_empty() {
+                    let group_by = group_by.iter().map(|column| quote_ident(column));
+                    sql += &format!(" GROUP BY {}", join(group_by));
+                }
+                if !order_by.is_empty() {
+                    let order_by = order_by.iter().map(|(column, direction)| {
+                        let direction = match direction {
+                            OrderDirection::Asc => "ASC",
+                            OrderDirection::Desc => "DESC",
+                        };
+                        format!("{} {}", quote_ident(column), direction)
+                    });
+                    sql += &format!(" ORDER BY {}", join(order_by));
+                }
+                if let Some(limit) = limit {
+                    sql += &format!(" LIMIT {}", limit);
+                }
+                if let Some(offset) = offset {
+                    sql += &format!(" OFFSET {}", offset);
+                }
+                if *for_update {
+                    sql += " FOR UPDATE";
+                }
+                sql
+            }
+            Statement::Delete { table_name, filter } => {
+                format!(
+                    "DELETE FROM {}{}",
+                    quote_ident(table_name),
+                    where_sql(filter)
+                )
+            }
+            Statement::Update {
+                table_name,
+                assignments,
+                filter,
+            } => {
+                let assignments = assignments
+                    .iter()
+                    .map(|(column, expr)| format!("{} = {}", quote_ident(column), expr.to_sql()));
+                format!(
+                    "UPDATE {} SET {}{}",
+                    quote_ident(table_name),
+                    join(assignments),
+                    where_sql(filter)
+                )
+            }
+            Statement::Begin => "BEGIN".to_string(),
+            Statement::Commit => "COMMIT".to_string(),
+            Statement::Rollback => "ROLLBACK".to_string(),
+        };
+        sql.push(';');
+        sql
+    }
+}
+
+fn join(items: impl Iterator<Item = String>) -> String {
+    items.collect::<Vec<_>>().join(", ")
+}
+
+fn where_sql(filter: &Option<Expression>) -> String {
+    match filter {
+        Some(filter) => format!(" WHERE {}", filter.to_sql()),
+        None => String::new(),
+    }
+}
+
+// Identifiers are left bare where the lexer would read them back unchanged, otherwise
+// they are double quoted.
+fn quote_ident(ident: &str) -> String {
+    let bare = ident.starts_with(|c: char| c.is_ascii_lowercase())
+        && ident
+            .chars()
+            .all(|c| c == '_' || (c.is_alphanumeric() && c.to_lowercase().eq([c])))
+        && Keyword::from_str(ident).is_none();
+    if bare {
+        ident.to_string()
+    } else {
+        format!("\"{}\"", ident.replace('"', "\"\""))
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum OrderDirection {
+    Asc,
+    Desc,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Column {
     pub name: String,
     pub datatype: DataType,
     pub nullable: Option<bool>,
     pub default: Option<Expression>,
+    pub comment: Option<String>,
+    pub primary_key: bool,
+}
+
+impl Column {
+    fn to_sql(&self) -> String {
+        let datatype = match self.datatype {
+            DataType::Boolean => "BOOLEAN",
+            DataType::Integer => "INTEGER",
+            DataType::BigInt => "BIGINT",
+            DataType::Float => "FLOAT",
+            DataType::String => "STRING",
+            DataType::Bytes => "BYTES",
+        };
+        let mut sql = format!("{} {}", quote_ident(&self.name), datatype);
+        if self.primary_key {
+            sql += " PRIMARY KEY";
+        }
+        match self.nullable {
+            Some(true) => sql += " NULL",
+            Some(false) => sql += " NOT NULL",
+            None => {}
+        }
+        if let Some(default) = &self.default {
+            sql += &format!(" DEFAULT {}", default.to_sql());
+        }
+        if let Some(comment) = &self.comment {
+            sql += &format!(" COMMENT {}", quote_string(comment));
+        }
+        sql
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Expression {
     Consts(Consts),
+    Field(String),
+    Operation(Operation),
+    // A function call such as COUNT(*), the name is uppercase and `*` has no arguments.
+    Function {
+        name: String,
+        args: Vec<Expression>,
+    },
+    // The values of a single column SELECT, only as the right operand of a comparison,
+    // e.g. a > ALL (SELECT b FROM t).
+    Subquery {
+        quantifier: Quantifier,
+        query: Box<Statement>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Quantifier {
+    // The comparison holds for some value.
+    Any,
+    // The comparison holds for every value.
+    All,
 }
 
 impl From<Consts> for Expression {
@@ -32,12 +267,165 @@ impl From<Consts> for Expression {
     }
 }
 
+impl Expression {
+    // Renders the expression as SQL. Nested operations are parenthesized, so the output
+    // doesn't depend on operator precedence.
+    pub fn to_sql(&self) -> String {
+        match self {
+            Expression::Consts(Consts::String(s)) => quote_string(s),
+            Expression::Consts(Consts::Null) => Value::Null.to_string(),
+            Expression::Consts(Consts::Boolean(b)) => Value::Boolean(*b).to_string(),
+            // Constants without a plain literal that parses back to them, e.g. from typed
+            // literals, render as typed literals.
+            Expression::Consts(Consts::Integer(i)) if *i < 0 => format!("INTEGER '{}'", i),
+            Expression::Consts(Consts::Integer(i)) => i.to_string(),
+            Expression::Consts(Consts::BigInt(i)) if *i <= i64::MAX as i128 => {
+                format!("BIGINT '{}'", i)
+            }
+            Expression::Consts(Consts::BigInt(i)) => i.to_string(),
+            Expression::Consts(Consts::Float(f)) if !f.is_finite() || f.is_sign_negative() => {
+                format!("FLOAT '{}'", Value::Float(*f))
+            }
+            Expression::Consts(Consts::Float(f)) => Value::Float(*f).to_string(),
+            Expression::Consts(Consts::Bytes(b)) => Value::Bytes(b.clone()).to_string(),
+            Expression::Field(name) => quote_ident(name),
+            Expression::Operation(operation) => operation.to_sql(),
+            Expression::Function { name, args } if args.is_empty() => format!("{}(*)", name),
+            Expression::Function { name, args } => {
+                format!("{}({})", name, join(args.iter().map(Expression::to_sql)))
+            }
+            Expression::Subquery { quantifier, query } => {
+                let quantifier = match quantifier {
+                    Quantifier::Any => "ANY",
+                    Quantifier::All => "ALL",
+                };
+                let query = query.to_sql();
+                format!("{} ({})", quantifier, query.trim_end_matches(';'))
+            }
+        }
+    }
+
+    // Name of the result column holding the expression's values: the column name for a
+    // plain column, otherwise the expression's SQL text.
+    pub fn column_name(&self) -> String {
+        match self {
+            Expression::Field(name) => name.clone(),
+            expr => expr.to_sql(),
+        }
+    }
+
+    // The function calls in the expression, outermost first.
+    pub fn functions(&self) -> Vec<&Expression> {
+        match self {
+            Expression::Function { .. } => vec![self],
+            Expression::Operation(operation) => operation
+                .operands()
+                .into_iter()
+                .flat_map(Expression::functions)
+                .collect(),
+            Expression::Consts(_) | Expression::Field(_) | Expression::Subquery { .. } => vec![],
+        }
+    }
+
+    fn to_operand_sql(&self) -> String {
+        let sql = self.to_sql();
+        // Negative constants too, so that they can't run into a preceding minus as `--`.
+        // Subqueries are already parenthesized.
+        if matches!(self, Expression::Operation(_)) || sql.starts_with('-') {
+            format!("({})", sql)
+        } else {
+            sql
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operation {
+    Equal(Box<Expression>, Box<Expression>),
+    NotEqual(Box<Expression>, Box<Expression>),
+    LessThan(Box<Expression>, Box<Expression>),
+    LessThanOrEqual(Box<Expression>, Box<Expression>),
+    GreaterThan(Box<Expression>, Box<Expression>),
+    GreaterThanOrEqual(Box<Expression>, Box<Expression>),
+    Add(Box<Expression>, Box<Expression>),
+    Subtract(Box<Expression>, Box<Expression>),
+    Multiply(Box<Expression>, Box<Expression>),
+    Divide(Box<Expression>, Box<Expression>),
+    Negate(Box<Expression>),
+    And(Box<Expression>, Box<Expression>),
+    Or(Box<Expression>, Box<Expression>),
+    Not(Box<Expression>),
+}
+
+impl Operation {
+    pub fn operands(&self) -> Vec<&Expression> {
+        match self {
+            Operation::Negate(expr) | Operation::Not(expr) => vec![expr],
+            Operation::Equal(l, r)
+            | Operation::NotEqual(l, r)
+            | Operation::LessThan(l, r)
+            | Operation::LessThanOrEqual(l, r)
+            | Operation::GreaterThan(l, r)
+            | Operation::GreaterThanOrEqual(l, r)
+            | Operation::Add(l, r)
+            | Operation::Subtract(l, r)
+            | Operation::Multiply(l, r)
+            | Operation::Divide(l, r)
+            | Operation::And(l, r)
+            | Operation::Or(l, r) => vec![l, r],
+        }
+    }
+
+    pub fn operands_mut(&mut self) -> Vec<&mut Expression> {
+        match self {
+            Operation::Negate(expr) | Operation::Not(expr) => vec![expr],
+            Operation::Equal(l, r)
+            | Operation::NotEqual(l, r)
+            | Operation::LessThan(l, r)
+            | Operation::LessThanOrEqual(l, r)
+            | Operation::GreaterThan(l, r)
+            | Operation::GreaterThanOrEqual(l, r)
+            | Operation::Add(l, r)
+            | Operation::Subtract(l, r)
+            | Operation::Multiply(l, r)
+            | Operation::Divide(l, r)
+            | Operation::And(l, r)
+            | Operation::Or(l, r) => vec![l, r],
+        }
+    }
+
+    fn to_sql(&self) -> String {
+        let (l, op, r) = match self {
+            Operation::Negate(expr) => return format!("-{}", expr.to_operand_sql()),
+            Operation::Not(expr) => return format!("NOT {}", expr.to_operand_sql()),
+            Operation::Equal(l, r) => (l, "=", r),
+            Operation::NotEqual(l, r) => (l, "!=", r),
+            Operation::LessThan(l, r) => (l, "<", r),
+            Operation::LessThanOrEqual(l, r) => (l, "<=", r),
+            Operation::GreaterThan(l, r) => (l, ">", r),
+            Operation::GreaterThanOrEqual(l, r) => (l, ">=", r),
+            Operation::Add(l, r) => (l, "+", r),
+            Operation::Subtract(l, r) => (l, "-", r),
+            Operation::Multiply(l, r) => (l, "*", r),
+            Operation::Divide(l, r) => (l, "/", r),
+            Operation::And(l, r) => (l, "AND", r),
+            Operation::Or(l, r) => (l, "OR", r),
+        };
+        format!("{} {} {}", l.to_operand_sql(), op, r.to_operand_sql())
+    }
+}
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Consts {
     Null,
     Boolean(bool),
     Integer(i64),
+    BigInt(i128),
     Float(f64),
-    String(String)
+    String(String),
+    Bytes(Vec<u8>),
+}
+
+fn quote_string(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
 }
@@ -1,12 +1,13 @@
 use crate::error::{Error, Result};
 use std::{fmt::Display, iter::Peekable, str::Chars};
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Token {
     Keyword(Keyword),
     Ident(String),
     String(String),
     Number(String),
+    Hex(String),
     OpenParen,
     CloseParen,
     Comma,
@@ -15,6 +16,12 @@ pub enum Token {
     Plus,
     Minus,
     Slash,
+    Equal,
+    NotEqual,
+    LessThan,
+    LessThanOrEqual,
+    GreaterThan,
+    GreaterThanOrEqual,
 }
 
 impl Display for Token {
@@ -24,6 +31,7 @@ impl Display for Token {
         Token::Ident(ident) => ident,
         Token::String(string) => string,
         Token::Number(number) => number,
+        Token::Hex(hex) => hex,
         Token::OpenParen => "(",
         Token::CloseParen => ")",
         Token::Comma => ",",
@@ -32,28 +40,59 @@ impl Display for Token {
         Token::Plus => "+",
         Token::Minus => "-",
         Token::Slash => "/",
+        Token::Equal => "=",
+        Token::NotEqual => "!=",
+        Token::LessThan => "<",
+        Token::LessThanOrEqual => "<=",
+        Token::GreaterThan => ">",
+        Token::GreaterThanOrEqual => ">=",
     })
    } 
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Keyword {
     Create,
     Table,
+    Temporary,
+    Temp,
     Int,
     Integer,
+    Bigint,
     Boolean,
     Bool,
     String,
     Text,
     Varchar,
+    Blob,
+    Bytes,
     Float,
     Double,
     Select,
     From,
     Insert,
+    Replace,
+    Delete,
+    Update,
+    Set,
     Into,
     Values,
+    Where,
+    Group,
+    Order,
+    By,
+    Asc,
+    Desc,
+    Limit,
+    Offset,
+    For,
+    And,
+    Or,
+    Any,
+    All,
+    Begin,
+    Commit,
+    Rollback,
     True,
     False,
     Default,
@@ -61,6 +100,7 @@ pub enum Keyword {
     Null,
     Primary,
     Key,
+    Comment,
 }
 
 impl Keyword {
@@ -68,20 +108,45 @@ impl Keyword {
         Some(match ident.to_uppercase().as_ref() {
             "CREATE" => Keyword::Create,
             "TABLE" => Keyword::Table,
+            "TEMPORARY" => Keyword::Temporary,
+            "TEMP" => Keyword::Temp,
             "INT" => Keyword::Int,
             "INTEGER" => Keyword::Integer,
+            "BIGINT" => Keyword::Bigint,
             "BOOLEAN" => Keyword::Boolean,
             "BOOL" => Keyword::Bool,
             "STRING" => Keyword::String,
             "TEXT" => Keyword::Text,
             "VARCHAR" => Keyword::Varchar,
+            "BLOB" => Keyword::Blob,
+            "BYTES" => Keyword::Bytes,
             "FLOAT" => Keyword::Float,
             "DOUBLE" => Keyword::Double,
             "SELECT" => Keyword::Select,
             "FROM" => Keyword::From,
             "INSERT" => Keyword::Insert,
+            "REPLACE" => Keyword::Replace,
+            "DELETE" => Keyword::Delete,
+            "UPDATE" => Keyword::Update,
+            "SET" => Keyword::Set,
             "INTO" => Keyword::Into,
             "VALUES" => Keyword::Values,
+            "WHERE" => Keyword::Where,
+            "GROUP" => Keyword::Group,
+            "ORDER" => Keyword::Order,
+            "BY" => Keyword::By,
+            "ASC" => Keyword::Asc,
+            "DESC" => Keyword::Desc,
+            "LIMIT" => Keyword::Limit,
+            "OFFSET" => Keyword::Offset,
+            "FOR" => Keyword::For,
+            "AND" => Keyword::And,
+            "OR" => Keyword::Or,
+            "ANY" => Keyword::Any,
+            "ALL" => Keyword::All,
+            "BEGIN" => Keyword::Begin,
+            "COMMIT" => Keyword::Commit,
+            "ROLLBACK" => Keyword::Rollback,
             "TRUE" => Keyword::True,
             "FALSE" => Keyword::False,
             "DEFAULT" => Keyword::Default,
@@ -89,6 +154,7 @@ impl Keyword {
             "NULL" => Keyword::Null,
             "PRIMARY" => Keyword::Primary,
             "KEY" => Keyword::Key,
+            "COMMENT" => Keyword::Comment,
             _ => return None,
         })
     }
@@ -97,20 +163,45 @@ impl Keyword {
         match self {
             Keyword::Create => "CREATE",
             Keyword::Table => "TABLE",
+            Keyword::Temporary => "TEMPORARY",
+            Keyword::Temp => "TEMP",
             Keyword::Int => "INT",
             Keyword::Integer => "INTEGER",
+            Keyword::Bigint => "BIGINT",
             Keyword::Boolean => "BOOLEAN",
             Keyword::Bool => "BOOL",
             Keyword::String => "STRING",
             Keyword::Text => "TEXT",
             Keyword::Varchar => "VARCHAR",
+            Keyword::Blob => "BLOB",
+            Keyword::Bytes => "BYTES",
             Keyword::Float => "FLOAT",
             Keyword::Double => "DOUBLE",
             Keyword::Select => "SELECT",
             Keyword::From => "FROM",
             Keyword::Insert => "INSERT",
+            Keyword::Replace => "REPLACE",
+            Keyword::Delete => "DELETE",
+            Keyword::Update => "UPDATE",
+            Keyword::Set => "SET",
             Keyword::Into => "INTO",
             Keyword::Values => "VALUES",
+            Keyword::Where => "WHERE",
+            Keyword::Group => "GROUP",
+            Keyword::Order => "ORDER",
+            Keyword::By => "BY",
+            Keyword::Asc => "ASC",
+            Keyword::Desc => "DESC",
+            Keyword::Limit => "LIMIT",
+            Keyword::Offset => "OFFSET",
+            Keyword::For => "FOR",
+            Keyword::And => "AND",
+            Keyword::Or => "OR",
+            Keyword::Any => "ANY",
+            Keyword::All => "ALL",
+            Keyword::Begin => "BEGIN",
+            Keyword::Commit => "COMMIT",
+            Keyword::Rollback => "ROLLBACK",
             Keyword::True => "TRUE",
             Keyword::False => "FALSE",
             Keyword::Default => "DEFAULT",
@@ -118,6 +209,7 @@ impl Keyword {
             Keyword::Null => "NULL",
             Keyword::Primary => "PRIMARY",
             Keyword::Key => "KEY",
+            Keyword::Comment => "COMMENT",
         }
     }
 }
@@ -128,6 +220,39 @@ impl Display for Keyword {
     }
 }
 
+// Whether the input holds a `;` outside of string literals and comments, e.g. so that a
+// REPL knows it has read a whole statement and can stop buffering lines.
+pub fn is_statement_complete(input: &str) -> bool {
+    statement_end(input).is_some()
+}
+
+// The byte offset just past the first `;` outside of string literals and comments.
+pub fn statement_end(input: &str) -> Option<usize> {
+    let mut iter = input.char_indices().peekable();
+    while let Some((i, c)) = iter.next() {
+        match c {
+            ';' => return Some(i + 1),
+            '\'' if !iter.any(|(_, c)| c == '\'') => return None,
+            '"' if !iter.any(|(_, c)| c == '"') => return None,
+            '-' if matches!(iter.peek(), Some((_, '-'))) && !iter.any(|(_, c)| c == '\n') => {
+                return None
+            }
+            '/' if matches!(iter.peek(), Some((_, '*'))) => {
+                iter.next();
+                loop {
+                    match iter.next() {
+                        Some((_, '*')) if iter.next_if(|(_, c)| *c == '/').is_some() => break,
+                        Some(_) => {}
+                        None => return None,
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
 #[derive(Debug)]
 pub struct Lexer<'a> {
     iter: Peekable<Chars<'a>>,
@@ -158,6 +283,32 @@ impl<'a> Lexer<'a> {
         self.next_while(|c| c.is_whitespace());
     }
 
+    // Skips a `-- line` or `/* block */` comment, returning whether one was found.
+    fn erase_comment(&mut self) -> Result<bool> {
+        let mut ahead = self.iter.clone();
+        match (ahead.next(), ahead.next()) {
+            (Some('-'), Some('-')) => {
+                self.next_while(|c| c != '\n');
+            }
+            (Some('/'), Some('*')) => {
+                self.iter.nth(1);
+                loop {
+                    match self.iter.next() {
+                        Some('*') if self.next_if(|c| c == '/').is_some() => break,
+                        Some(_) => {}
+                        None => {
+                            return Err(Error::Parse(
+                                "[Lexer] Unexpected end of comment".to_string(),
+                            ))
+                        }
+                    }
+                }
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
     fn next_if<F: Fn(char) -> bool>(&mut self, predicate: F) -> Option<char> {
         self.iter.peek().filter(|&c| predicate(*c))?;
         self.iter.next()
@@ -181,9 +332,15 @@ impl<'a> Lexer<'a> {
 
     fn scan(&mut self) -> Result<Option<Token>> {
         self.erase_whitespace();
+        while self.erase_comment()? {
+            self.erase_whitespace();
+        }
+        let hex_quote = self.iter.clone().nth(1) == Some('\'');
         match self.iter.peek() {
             Some('\'') => self.scan_string(),
-            Some(c) if c.is_ascii_digit() => Ok(self.scan_number()),
+            Some('"') => self.scan_quoted_ident(),
+            Some('x') | Some('X') if hex_quote => self.scan_hex(),
+            Some(c) if c.is_ascii_digit() => self.scan_number(),
             Some(c) if c.is_ascii_alphabetic() => Ok(self.scan_ident()),
             Some(_) => Ok(self.scan_symbol()),
             None => Ok(None),
@@ -198,6 +355,8 @@ impl<'a> Lexer<'a> {
         let mut value = String::new();
         loop {
             match self.iter.next() {
+                // A doubled quote is an escaped quote.
+                Some('\'') if self.next_if(|c| c == '\'').is_some() => value.push('\''),
                 Some('\'') => break,
                 Some(c) => value.push(c),
                 None => return Err(Error::Parse(format!("[Lexer] Unexpected end of string"))),
@@ -207,16 +366,81 @@ impl<'a> Lexer<'a> {
         Ok(Some(Token::String(value)))
     }
 
-    fn scan_number(&mut self) -> Option<Token> {
-        let mut num = self.next_while(|c| c.is_ascii_digit())?;
+    // An identifier in double quotes, which keeps its case and may be a keyword.
+    fn scan_quoted_ident(&mut self) -> Result<Option<Token>> {
+        if self.next_if(|c| c == '"').is_none() {
+            return Ok(None);
+        }
+
+        let mut value = String::new();
+        loop {
+            match self.iter.next() {
+                Some('"') if self.next_if(|c| c == '"').is_some() => value.push('"'),
+                Some('"') => break,
+                Some(c) => value.push(c),
+                None => {
+                    return Err(Error::Parse(
+                        "[Lexer] Unexpected end of quoted identifier".to_string(),
+                    ))
+                }
+            }
+        }
+        if value.is_empty() {
+            return Err(Error::Parse("[Lexer] Empty quoted identifier".to_string()));
+        }
+
+        Ok(Some(Token::Ident(value)))
+    }
+
+    // A hex blob literal such as x'00FF', holding an even number of hex digits.
+    fn scan_hex(&mut self) -> Result<Option<Token>> {
+        self.iter.next();
+        let hex = match self.scan_string()? {
+            Some(Token::String(hex)) => hex,
+            _ => return Ok(None),
+        };
+        if hex.len() % 2 != 0 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(Error::Parse(format!(
+                "[Lexer] Invalid hex literal x'{}'",
+                hex
+            )));
+        }
+
+        Ok(Some(Token::Hex(hex)))
+    }
+
+    fn scan_number(&mut self) -> Result<Option<Token>> {
+        let mut num = match self.scan_digits()? {
+            Some(num) => num,
+            None => return Ok(None),
+        };
         if let Some(sep) = self.next_if(|c| c == '.') {
             num.push(sep);
-            while let Some(c) = self.next_if(|c| c.is_ascii_digit()) {
-                num.push(c)
+            if let Some(frac) = self.scan_digits()? {
+                num.push_str(&frac);
+            }
+        }
+
+        Ok(Some(Token::Number(num)))
+    }
+
+    // Digits with optional `_` separators, which are only allowed between two digits.
+    fn scan_digits(&mut self) -> Result<Option<String>> {
+        let mut digits = String::new();
+        while let Some(c) = self.next_if(|c| c.is_ascii_digit() || c == '_') {
+            if c == '_' {
+                if digits.is_empty() || !self.iter.peek().is_some_and(|c| c.is_ascii_digit()) {
+                    return Err(Error::Parse(format!(
+                        "[Lexer] Invalid digit separator in number {}_",
+                        digits
+                    )));
+                }
+                continue;
             }
+            digits.push(c);
         }
 
-        Some(Token::Number(num))
+        Ok(Some(digits).filter(|d| !d.is_empty()))
     }
 
     fn scan_ident(&mut self) -> Option<Token> {
@@ -230,6 +454,18 @@ impl<'a> Lexer<'a> {
     }
 
     fn scan_symbol(&mut self) -> Option<Token> {
+        let mut ahead = self.iter.clone();
+        let token = match (ahead.next()?, ahead.next()) {
+            ('!', Some('=')) | ('<', Some('>')) => Token::NotEqual,
+            ('<', Some('=')) => Token::LessThanOrEqual,
+            ('>', Some('=')) => Token::GreaterThanOrEqual,
+            _ => return self.scan_single_symbol(),
+        };
+        self.iter.nth(1);
+        Some(token)
+    }
+
+    fn scan_single_symbol(&mut self) -> Option<Token> {
         self.next_if_token(|c| {
             Some(match c {
                 '*' => Token::Asterisk,
@@ -240,6 +476,9 @@ impl<'a> Lexer<'a> {
                 '+' => Token::Plus,
                 '-' => Token::Minus,
                 '/' => Token::Slash,
+                '=' => Token::Equal,
+                '<' => Token::LessThan,
+                '>' => Token::GreaterThan,
                 _ => return None,
             })
         })
@@ -248,7 +487,7 @@ impl<'a> Lexer<'a> {
 
 #[cfg(test)]
 mod tests {
-    use super::{Lexer, Token};
+    use super::{is_statement_complete, statement_end, Lexer, Token};
     use crate::{error::Result, sql::parser::lexer::Keyword};
 
     #[test]
@@ -359,4 +598,121 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_lexer_number_separator() -> Result<()> {
+        let tokens = Lexer::new("1_000 3.14_15 1_000_000.5")
+            .peekable()
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Number("1000".to_string()),
+                Token::Number("3.1415".to_string()),
+                Token::Number("1000000.5".to_string()),
+            ]
+        );
+
+        for sql in ["1__0", "1_", "1_.5", "1._5"] {
+            assert!(Lexer::new(sql).collect::<Result<Vec<_>>>().is_err());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lexer_hex_literal() -> Result<()> {
+        let tokens = Lexer::new("x'00FF', X'', xy")
+            .peekable()
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Hex("00FF".to_string()),
+                Token::Comma,
+                Token::Hex("".to_string()),
+                Token::Comma,
+                Token::Ident("xy".to_string()),
+            ]
+        );
+
+        for sql in ["x'0'", "x'GG'", "x'00"] {
+            assert!(Lexer::new(sql).collect::<Result<Vec<_>>>().is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_lexer_comparison_operators() -> Result<()> {
+        let tokens = Lexer::new("a=1 != <> < <= > >=b").collect::<Result<Vec<_>>>()?;
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Ident("a".to_string()),
+                Token::Equal,
+                Token::Number("1".to_string()),
+                Token::NotEqual,
+                Token::NotEqual,
+                Token::LessThan,
+                Token::LessThanOrEqual,
+                Token::GreaterThan,
+                Token::GreaterThanOrEqual,
+                Token::Ident("b".to_string()),
+            ]
+        );
+        let text = tokens.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+        assert_eq!(text.join(" "), "a = 1 != != < <= > >= b");
+        assert!(Lexer::new("a ! b").collect::<Result<Vec<_>>>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_lexer_quoting() -> Result<()> {
+        let tokens =
+            Lexer::new(r#"'it''s' '''' "Select" "a""b" "x;y""#).collect::<Result<Vec<_>>>()?;
+        assert_eq!(
+            tokens,
+            vec![
+                Token::String("it's".to_string()),
+                Token::String("'".to_string()),
+                Token::Ident("Select".to_string()),
+                Token::Ident("a\"b".to_string()),
+                Token::Ident("x;y".to_string()),
+            ]
+        );
+
+        for sql in [r#""""#, r#""abc"#, r#""a"""#] {
+            assert!(Lexer::new(sql).collect::<Result<Vec<_>>>().is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_statement_complete() {
+        for sql in [
+            "select * from t;",
+            "select * from t where a = 'x;y';",
+            "select * -- comment;\n from t;",
+            "select * /* ; */ from t;",
+            "insert into t values ('it''s');",
+            "select * from t; select",
+        ] {
+            assert!(is_statement_complete(sql), "{}", sql);
+        }
+
+        for sql in [
+            "",
+            "select * from t",
+            "select * from t where a = 'x;",
+            "select \"a;",
+            "select * from t -- ;",
+            "select * from t /* ; */",
+            "select * from t /* ;",
+        ] {
+            assert!(!is_statement_complete(sql), "{}", sql);
+        }
+
+        assert_eq!(statement_end("select 'a;b'; select 1;"), Some(13));
+        assert_eq!(statement_end("select 'é';"), Some(12));
+    }
 }
@@ -1,27 +1,75 @@
 pub mod ast;
 mod lexer;
 
+pub use lexer::{is_statement_complete, statement_end, Keyword};
+
 use crate::error::{Error, Result};
 use ast::Column;
-use lexer::{Keyword, Lexer, Token};
+use lexer::{Lexer, Token};
 use std::iter::Peekable;
 
-use super::types::DataType;
+use super::types::{DataType, Value};
+
+const DEFAULT_MAX_IDENTIFIER_LENGTH: usize = 64;
+
+// NOT binds looser than comparisons, so that NOT a = b negates the comparison.
+const NOT_PRECEDENCE: u8 = 3;
+const COMPARISON_PRECEDENCE: u8 = 3;
+
+type BinaryOperation = fn(Box<ast::Expression>, Box<ast::Expression>) -> ast::Operation;
+
+// The tokens of SQL text, so statements differing only in whitespace, comments or keyword
+// and identifier case normalize to equal keys.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NormalizedSql(Vec<Token>);
+
+pub fn normalize(input: &str) -> Result<NormalizedSql> {
+    Ok(NormalizedSql(Lexer::new(input).collect::<Result<_>>()?))
+}
 
 pub struct Parser<'a> {
     lexer: Peekable<Lexer<'a>>,
+    relaxed_keywords: Vec<Keyword>,
+    default_datatype: Option<DataType>,
+    max_identifier_length: usize,
 }
 
 impl<'a> Parser<'a> {
     pub fn new(input: &'a str) -> Self {
         Self {
             lexer: Lexer::new(input).peekable(),
+            relaxed_keywords: Vec::new(),
+            default_datatype: None,
+            max_identifier_length: DEFAULT_MAX_IDENTIFIER_LENGTH,
         }
     }
 
+    // Keywords which are accepted as identifiers wherever an identifier is expected.
+    pub fn with_relaxed_keywords(mut self, keywords: Vec<Keyword>) -> Self {
+        self.relaxed_keywords = keywords;
+        self
+    }
+
+    // Datatype given to columns declared without one. Without it a type is required.
+    pub fn with_default_datatype(mut self, datatype: DataType) -> Self {
+        self.default_datatype = Some(datatype);
+        self
+    }
+
+    // Longest table or column name accepted, in characters.
+    pub fn with_max_identifier_length(mut self, len: usize) -> Self {
+        self.max_identifier_length = len;
+        self
+    }
+
     pub fn parse(&mut self) -> Result<ast::Statement> {
+        if self.peek()?.is_none() {
+            return Err(Error::Parse("[Parser] Empty statement".to_string()));
+        }
         let stmt = self.parse_statement()?;
         self.next_expect(Token::Semicolon)?;
+        // Extra terminators are empty statements, so they are ignored.
+        while self.next_if_token(Token::Semicolon).is_some() {}
         if let Some(token) = self.peek()? {
             return Err(Error::Parse(format!(
                 "[Parser] Unexpected token {:?}",
@@ -35,7 +83,16 @@ impl<'a> Parser<'a> {
         match self.peek()? {
             Some(Token::Keyword(Keyword::Create)) => self.parse_ddl(),
             Some(Token::Keyword(Keyword::Select)) => self.parse_select(),
-            Some(Token::Keyword(Keyword::Insert)) => self.parse_insert(),
+            Some(Token::Keyword(Keyword::Insert)) | Some(Token::Keyword(Keyword::Replace)) => {
+                self.parse_insert()
+            }
+            Some(Token::Keyword(Keyword::Delete)) => self.parse_delete(),
+            Some(Token::Keyword(Keyword::Update)) => self.parse_update(),
+            Some(Token::Keyword(Keyword::Begin)) => self.parse_transaction(ast::Statement::Begin),
+            Some(Token::Keyword(Keyword::Commit)) => self.parse_transaction(ast::Statement::Commit),
+            Some(Token::Keyword(Keyword::Rollback)) => {
+                self.parse_transaction(ast::Statement::Rollback)
+            }
             Some(t) => Err(Error::Parse(format!("[Parser] Unexpected token {}", t))),
             None => Err(Error::Parse(format!("[Parser] Unexpected end of input"))),
         }
@@ -43,18 +100,171 @@ impl<'a> Parser<'a> {
 
     fn parse_select(&mut self) -> Result<ast::Statement> {
         self.next_expect(Token::Keyword(Keyword::Select))?;
-        self.next_expect(Token::Asterisk)?;
+        let columns = match self.next_if_token(Token::Asterisk) {
+            Some(_) => None,
+            None => {
+                let mut columns = vec![self.parse_expression()?];
+                while self.next_if_token(Token::Comma).is_some() {
+                    columns.push(self.parse_expression()?);
+                }
+                Some(columns)
+            }
+        };
+
+        let table_name = match self.next_if_token(Token::Keyword(Keyword::From)) {
+            Some(_) => Some(self.next_ident()?),
+            None if columns.is_none() => {
+                return Err(Error::Parse("[Parser] SELECT * requires FROM".to_string()))
+            }
+            None => None,
+        };
+        let filter = self.parse_where()?;
+        let group_by = self.parse_group_by()?;
+        let order_by = self.parse_order_by()?;
+        let limit = self.parse_count(Keyword::Limit)?;
+        let offset = self.parse_count(Keyword::Offset)?;
+        let for_update = self.next_if_token(Token::Keyword(Keyword::For)).is_some();
+        if for_update {
+            self.next_expect(Token::Keyword(Keyword::Update))?;
+            if table_name.is_none() {
+                return Err(Error::Parse(
+                    "[Parser] FOR UPDATE requires FROM".to_string(),
+                ));
+            }
+        }
+        Ok(ast::Statement::Select {
+            table_name,
+            columns,
+            filter,
+            group_by,
+            order_by,
+            limit,
+            offset,
+            for_update,
+        })
+    }
+
+    // BEGIN, COMMIT and ROLLBACK are a lone keyword.
+    fn parse_transaction(&mut self, stmt: ast::Statement) -> Result<ast::Statement> {
+        self.next()?;
+        Ok(stmt)
+    }
+
+    fn parse_delete(&mut self) -> Result<ast::Statement> {
+        self.next_expect(Token::Keyword(Keyword::Delete))?;
         self.next_expect(Token::Keyword(Keyword::From))?;
 
-        let table_name = self.next_ident()?; 
-        Ok(ast::Statement::Select { table_name })
+        let table_name = self.next_ident()?;
+        let filter = self.parse_where()?;
+        Ok(ast::Statement::Delete { table_name, filter })
+    }
+
+    fn parse_update(&mut self) -> Result<ast::Statement> {
+        self.next_expect(Token::Keyword(Keyword::Update))?;
+        let table_name = self.next_ident()?;
+        self.next_expect(Token::Keyword(Keyword::Set))?;
+
+        let mut assignments = Vec::new();
+        loop {
+            let column = self.next_ident()?;
+            self.next_expect(Token::Equal)?;
+            assignments.push((column, self.parse_expression()?));
+            if self.next_if_token(Token::Comma).is_none() {
+                break;
+            }
+        }
+
+        let filter = self.parse_where()?;
+        Ok(ast::Statement::Update {
+            table_name,
+            assignments,
+            filter,
+        })
+    }
+
+    fn parse_where(&mut self) -> Result<Option<ast::Expression>> {
+        if self.next_if_token(Token::Keyword(Keyword::Where)).is_none() {
+            return Ok(None);
+        }
+        Ok(Some(self.parse_expression()?))
+    }
+
+    fn parse_group_by(&mut self) -> Result<Vec<String>> {
+        let mut group_by = Vec::new();
+        if self.next_if_token(Token::Keyword(Keyword::Group)).is_none() {
+            return Ok(group_by);
+        }
+        self.next_expect(Token::Keyword(Keyword::By))?;
+
+        loop {
+            group_by.push(self.next_ident()?);
+            if self.next_if_token(Token::Comma).is_none() {
+                break;
+            }
+        }
+        Ok(group_by)
+    }
+
+    fn parse_order_by(&mut self) -> Result<Vec<(String, ast::OrderDirection)>> {
+        let mut order_by = Vec::new();
+        if self.next_if_token(Token::Keyword(Keyword::Order)).is_none() {
+            return Ok(order_by);
+        }
+        self.next_expect(Token::Keyword(Keyword::By))?;
+
+        loop {
+            let column = self.next_ident()?;
+            let direction = match self.next_if_token(Token::Keyword(Keyword::Desc)) {
+                Some(_) => ast::OrderDirection::Desc,
+                None => {
+                    self.next_if_token(Token::Keyword(Keyword::Asc));
+                    ast::OrderDirection::Asc
+                }
+            };
+            order_by.push((column, direction));
+            if self.next_if_token(Token::Comma).is_none() {
+                break;
+            }
+        }
+        Ok(order_by)
+    }
+
+    // Parses an optional `keyword n` clause such as LIMIT 10.
+    fn parse_count(&mut self, keyword: Keyword) -> Result<Option<usize>> {
+        let token = Token::Keyword(keyword.clone());
+        if self.next_if_token(token).is_none() {
+            return Ok(None);
+        }
+        match self.next()? {
+            Token::Number(n) => n
+                .parse()
+                .map(Some)
+                .map_err(|_| Error::Parse(format!("[Parser] Invalid {} {}", keyword, n))),
+            token => Err(Error::Parse(format!("[Parser] Unexpected token {}", token))),
+        }
     }
 
     fn parse_insert(&mut self) -> Result<ast::Statement> {
-        self.next_expect(Token::Keyword(Keyword::Insert))?;    
+        let replace = match self.next()? {
+            Token::Keyword(Keyword::Insert) => false,
+            Token::Keyword(Keyword::Replace) => true,
+            token => return Err(Error::Parse(format!("[Parser] Unexpected token {}", token))),
+        };
         self.next_expect(Token::Keyword(Keyword::Into))?;    
 
         let table_name = self.next_ident()?;
+        // DEFAULT VALUES inserts a single row made of the column defaults.
+        let default = Token::Keyword(Keyword::Default);
+        if self.next_if_token(default).is_some() {
+            self.next_expect(Token::Keyword(Keyword::Values))?;
+            return Ok(ast::Statement::Insert {
+                table_name,
+                columns: None,
+                values: vec![Vec::new()],
+                replace,
+            });
+        }
+
         let columns = if self.next_if_token(Token::OpenParen).is_some() {
             let mut cols = Vec::new();
             loop {
@@ -91,20 +301,29 @@ impl<'a> Parser<'a> {
             }
         }
 
-        Ok(ast::Statement::Insert { table_name, columns, values })
+        Ok(ast::Statement::Insert {
+            table_name,
+            columns,
+            values,
+            replace,
+        })
     }
 
     fn parse_ddl(&mut self) -> Result<ast::Statement> {
         match self.next()? {
             Token::Keyword(Keyword::Create) => match self.next()? {
-                Token::Keyword(Keyword::Table) => self.parse_ddl_create_table(),
+                Token::Keyword(Keyword::Table) => self.parse_ddl_create_table(false),
+                Token::Keyword(Keyword::Temporary) | Token::Keyword(Keyword::Temp) => {
+                    self.next_expect(Token::Keyword(Keyword::Table))?;
+                    self.parse_ddl_create_table(true)
+                }
                 token => Err(Error::Parse(format!("[Parser] Unexpected token {}", token))),
             },
             token => Err(Error::Parse(format!("[Parser] Unexpected token {}", token))),
         }
     }
 
-    fn parse_ddl_create_table(&mut self) -> Result<ast::Statement> {
+    fn parse_ddl_create_table(&mut self, temporary: bool) -> Result<ast::Statement> {
         let table_name = self.next_ident()?;
         self.next_expect(Token::OpenParen)?;
         let mut columns = Vec::new();
@@ -119,27 +338,28 @@ impl<'a> Parser<'a> {
         Ok(ast::Statement::CreateTable {
             name: table_name,
             columns,
+            temporary,
         })
     }
 
     fn parse_ddl_column(&mut self) -> Result<ast::Column> {
+        let name = self.next_ident()?;
+        let datatype = match (self.next_if_datatype(), &self.default_datatype) {
+            (Some(datatype), _) => datatype,
+            (None, Some(datatype)) => datatype.clone(),
+            (None, None) => {
+                let token = self.next()?;
+                return Err(Error::Parse(format!("[Parser] Unexpected token {}", token)));
+            }
+        };
+
         let mut column = Column {
-            name: self.next_ident()?,
-            datatype: match self.next()? {
-                Token::Keyword(Keyword::Int) | Token::Keyword(Keyword::Integer) => {
-                    DataType::Integer
-                }
-                Token::Keyword(Keyword::Bool) | Token::Keyword(Keyword::Boolean) => {
-                    DataType::Boolean
-                }
-                Token::Keyword(Keyword::Float) | Token::Keyword(Keyword::Double) => DataType::Float,
-                Token::Keyword(Keyword::String)
-                | Token::Keyword(Keyword::Text)
-                | Token::Keyword(Keyword::Varchar) => DataType::String,
-                token => return Err(Error::Parse(format!("[Parser] Unexpected token {}", token))),
-            },
+            name,
+            datatype,
             nullable: None,
             default: None,
+            comment: None,
+            primary_key: false,
         };
 
         while let Some(Token::Keyword(keyword)) = self.next_if_keyword() {
@@ -149,7 +369,17 @@ impl<'a> Parser<'a> {
                     self.next_expect(Token::Keyword(Keyword::Null))?;
                     column.nullable = Some(false);
                 }
+                Keyword::Primary => {
+                    self.next_expect(Token::Keyword(Keyword::Key))?;
+                    column.primary_key = true;
+                }
                 Keyword::Default => column.default = Some(self.parse_expression()?),
+                Keyword::Comment => match self.next()? {
+                    Token::String(comment) => column.comment = Some(comment),
+                    token => {
+                        return Err(Error::Parse(format!("[Parser] Unexpected token {}", token)))
+                    }
+                },
                 k => return Err(Error::Parse(format!("[Parser] Unexpected keyword {}", k))),
             }
         }
@@ -158,18 +388,154 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_expression(&mut self) -> Result<ast::Expression> {
+        self.parse_expression_with(1)
+    }
+
+    // Precedence climbing: parses operands and any binary operators binding at least as
+    // tightly as min_precedence. Operators of equal precedence associate to the left.
+    fn parse_expression_with(&mut self, min_precedence: u8) -> Result<ast::Expression> {
+        // A NOT here is the prefix operator, the NOT of NOT NULL only follows a column
+        // definition or expression, where no operand is expected.
+        let mut left = match self.next_if_token(Token::Keyword(Keyword::Not)) {
+            Some(_) => {
+                let operand = self.parse_expression_with(min_precedence.max(NOT_PRECEDENCE))?;
+                ast::Expression::Operation(ast::Operation::Not(Box::new(operand)))
+            }
+            None => self.parse_expression_atom()?,
+        };
+        while let Some((precedence, operation)) =
+            self.peek()?.as_ref().and_then(Self::binary_operator)
+        {
+            if precedence < min_precedence {
+                break;
+            }
+            self.next()?;
+            let right = match self.parse_subquery(precedence)? {
+                Some(subquery) => subquery,
+                None => self.parse_expression_with(precedence + 1)?,
+            };
+            left = ast::Expression::Operation(operation(Box::new(left), Box::new(right)));
+        }
+        Ok(left)
+    }
+
+    // An ANY or ALL subquery, which may only follow a comparison operator.
+    fn parse_subquery(&mut self, precedence: u8) -> Result<Option<ast::Expression>> {
+        if precedence != COMPARISON_PRECEDENCE {
+            return Ok(None);
+        }
+        let quantifier = match self.peek()? {
+            Some(Token::Keyword(Keyword::Any)) => ast::Quantifier::Any,
+            Some(Token::Keyword(Keyword::All)) => ast::Quantifier::All,
+            _ => return Ok(None),
+        };
+        self.next()?;
+        self.next_expect(Token::OpenParen)?;
+        let query = self.parse_select()?;
+        self.next_expect(Token::CloseParen)?;
+        Ok(Some(ast::Expression::Subquery {
+            quantifier,
+            query: Box::new(query),
+        }))
+    }
+
+    // The precedence and constructor of a binary operator, higher binds tighter.
+    fn binary_operator(token: &Token) -> Option<(u8, BinaryOperation)> {
+        Some(match token {
+            Token::Keyword(Keyword::Or) => (1, ast::Operation::Or),
+            Token::Keyword(Keyword::And) => (2, ast::Operation::And),
+            Token::Equal => (COMPARISON_PRECEDENCE, ast::Operation::Equal),
+            Token::NotEqual => (COMPARISON_PRECEDENCE, ast::Operation::NotEqual),
+            Token::LessThan => (COMPARISON_PRECEDENCE, ast::Operation::LessThan),
+            Token::LessThanOrEqual => (COMPARISON_PRECEDENCE, ast::Operation::LessThanOrEqual),
+            Token::GreaterThan => (COMPARISON_PRECEDENCE, ast::Operation::GreaterThan),
+            Token::GreaterThanOrEqual => {
+                (COMPARISON_PRECEDENCE, ast::Operation::GreaterThanOrEqual)
+            }
+            Token::Plus => (4, ast::Operation::Add),
+            Token::Minus => (4, ast::Operation::Subtract),
+            Token::Asterisk => (5, ast::Operation::Multiply),
+            Token::Slash => (5, ast::Operation::Divide),
+            _ => return None,
+        })
+    }
+
+    fn parse_expression_atom(&mut self) -> Result<ast::Expression> {
+        // A typed literal such as INTEGER '42', coerced to a constant of the type. Without
+        // the string a relaxed type keyword names a column.
+        if let Some(Token::Keyword(keyword)) = self.peek()? {
+            if let Some(datatype) = self.next_if_datatype() {
+                return match self.next_if(|t| matches!(t, Token::String(_))) {
+                    Some(Token::String(s)) => {
+                        match Value::String(s.clone()).try_coerce_to(&datatype) {
+                            Ok(value) => Ok(ast::Consts::from(value).into()),
+                            Err(_) => Err(Error::Parse(format!(
+                                "[Parser] Invalid typed literal '{}'",
+                                s
+                            ))),
+                        }
+                    }
+                    _ if self.relaxed_keywords.contains(&keyword) => {
+                        Ok(ast::Expression::Field(keyword.to_str().to_lowercase()))
+                    }
+                    _ => Err(Error::Parse(format!(
+                        "[Parser] Unexpected token {:?}",
+                        self.next()?
+                    ))),
+                };
+            }
+        }
         Ok(match self.next()? {
+            Token::OpenParen => {
+                let expr = self.parse_expression()?;
+                self.next_expect(Token::CloseParen)?;
+                expr
+            }
+            // Unary minus binds tighter than any binary operator.
+            Token::Minus => ast::Expression::Operation(ast::Operation::Negate(Box::new(
+                self.parse_expression_atom()?,
+            ))),
             Token::Number(n) => {
                 if n.chars().all(|c| c.is_ascii_digit()) {
-                    ast::Consts::Integer(n.parse()?).into()
+                    match n.parse::<i64>() {
+                        Ok(i) => ast::Consts::Integer(i).into(),
+                        Err(_) => ast::Consts::BigInt(n.parse()?).into(),
+                    }
                 } else {
                     ast::Consts::Float(n.parse()?).into()
                 }
             }
             Token::String(s) => ast::Consts::String(s).into(),
+            Token::Ident(name) if self.next_if_token(Token::OpenParen).is_some() => {
+                let mut args = Vec::new();
+                if self.next_if_token(Token::Asterisk).is_none() {
+                    loop {
+                        args.push(self.parse_expression()?);
+                        if self.next_if_token(Token::Comma).is_none() {
+                            break;
+                        }
+                    }
+                }
+                self.next_expect(Token::CloseParen)?;
+                ast::Expression::Function {
+                    name: name.to_uppercase(),
+                    args,
+                }
+            }
+            Token::Ident(ident) => ast::Expression::Field(ident),
+            Token::Hex(h) => ast::Consts::Bytes(
+                (0..h.len())
+                    .step_by(2)
+                    .map(|i| u8::from_str_radix(&h[i..i + 2], 16))
+                    .collect::<std::result::Result<_, _>>()?,
+            )
+            .into(),
             Token::Keyword(Keyword::True) => ast::Consts::Boolean(true).into(),
             Token::Keyword(Keyword::False) => ast::Consts::Boolean(false).into(),
             Token::Keyword(Keyword::Null) => ast::Consts::Null.into(),
+            Token::Keyword(keyword) if self.relaxed_keywords.contains(&keyword) => {
+                ast::Expression::Field(keyword.to_str().to_lowercase())
+            }
             t => return Err(Error::Parse(format!("[Parser] Unexpected token {:?}", t))),
         })
     }
@@ -185,13 +551,25 @@ impl<'a> Parser<'a> {
     }
 
     fn next_ident(&mut self) -> Result<String> {
-        match self.next()? {
-            Token::Ident(ident) => Ok(ident),
-            token => Err(Error::Parse(format!(
-                "[Parser] Excepted ident, got token {}",
-                token
-            ))),
+        let ident = match self.next()? {
+            Token::Ident(ident) => ident,
+            Token::Keyword(keyword) if self.relaxed_keywords.contains(&keyword) => {
+                keyword.to_str().to_lowercase()
+            }
+            token => {
+                return Err(Error::Parse(format!(
+                    "[Parser] Excepted ident, got token {}",
+                    token
+                )))
+            }
+        };
+        if ident.chars().count() > self.max_identifier_length {
+            return Err(Error::Parse(format!(
+                "[Parser] Identifier too long: {}",
+                ident
+            )));
         }
+        Ok(ident)
     }
 
     fn next_expect(&mut self, expect: Token) -> Result<()> {
@@ -210,6 +588,22 @@ impl<'a> Parser<'a> {
         self.next().ok()
     }
 
+    fn next_if_datatype(&mut self) -> Option<DataType> {
+        let datatype = match self.peek().unwrap_or(None)? {
+            Token::Keyword(Keyword::Int) | Token::Keyword(Keyword::Integer) => DataType::Integer,
+            Token::Keyword(Keyword::Bigint) => DataType::BigInt,
+            Token::Keyword(Keyword::Bool) | Token::Keyword(Keyword::Boolean) => DataType::Boolean,
+            Token::Keyword(Keyword::Float) | Token::Keyword(Keyword::Double) => DataType::Float,
+            Token::Keyword(Keyword::String)
+            | Token::Keyword(Keyword::Text)
+            | Token::Keyword(Keyword::Varchar) => DataType::String,
+            Token::Keyword(Keyword::Blob) | Token::Keyword(Keyword::Bytes) => DataType::Bytes,
+            _ => return None,
+        };
+        self.next().ok()?;
+        Some(datatype)
+    }
+
     fn next_if_keyword(&mut self) -> Option<Token> {
         self.next_if(|t| matches!(t, Token::Keyword(_)))
     }
@@ -218,3 +612,449 @@ impl<'a> Parser<'a> {
         self.next_if(|t| t == &token)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{ast, Keyword, Parser};
+    use crate::{
+        error::{Error, Result},
+        sql::types::DataType,
+    };
+
+    #[test]
+    fn test_parser_relaxed_keywords() -> Result<()> {
+        let sql = "create table t (key int);";
+        assert!(Parser::new(sql).parse().is_err());
+
+        let stmt = Parser::new(sql)
+            .with_relaxed_keywords(vec![Keyword::Key])
+            .parse()?;
+        assert_eq!(
+            stmt,
+            ast::Statement::CreateTable {
+                name: "t".to_string(),
+                columns: vec![ast::Column {
+                    name: "key".to_string(),
+                    datatype: DataType::Integer,
+                    nullable: None,
+                    default: None,
+                    comment: None,
+                    primary_key: false,
+                }],
+                temporary: false,
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parser_empty_statement() -> Result<()> {
+        for sql in [
+            "",
+            "  \n\t ",
+            "-- nothing to run\n",
+            "/* block */ -- and line",
+        ] {
+            assert_eq!(
+                Parser::new(sql).parse(),
+                Err(Error::Parse("[Parser] Empty statement".to_string()))
+            );
+        }
+
+        let stmt = Parser::new("-- leading\nselect * /* inline */ from t1; -- trailing").parse()?;
+        assert_eq!(
+            stmt,
+            ast::Statement::Select {
+                table_name: Some("t1".to_string()),
+                columns: None,
+                filter: None,
+                group_by: Vec::new(),
+                order_by: Vec::new(),
+                limit: None,
+                offset: None,
+                for_update: false,
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parser_trailing_semicolons() -> Result<()> {
+        let select = ast::Statement::Select {
+            table_name: Some("t1".to_string()),
+            columns: None,
+            filter: None,
+            group_by: Vec::new(),
+            order_by: Vec::new(),
+            limit: None,
+            offset: None,
+            for_update: false,
+        };
+        for sql in [
+            "select * from t1;;",
+            "select * from t1; ;\n;",
+            "select * from t1;  \n\t",
+            "select * from t1; -- done\n; /* really */",
+        ] {
+            assert_eq!(Parser::new(sql).parse()?, select);
+        }
+
+        assert!(Parser::new("select * from t1").parse().is_err());
+        assert!(Parser::new(";select * from t1;").parse().is_err());
+        let sql = "select * from t1;; select * from t1;";
+        assert!(Parser::new(sql).parse().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parser_default_datatype() -> Result<()> {
+        let sql = "create table t (a, b int, c not null);";
+        assert!(Parser::new(sql).parse().is_err());
+
+        let stmt = Parser::new(sql)
+            .with_default_datatype(DataType::String)
+            .parse()?;
+        match stmt {
+            ast::Statement::CreateTable { columns, .. } => {
+                assert_eq!(
+                    columns
+                        .iter()
+                        .map(|c| c.datatype.clone())
+                        .collect::<Vec<_>>(),
+                    vec![DataType::String, DataType::Integer, DataType::String]
+                );
+                assert_eq!(columns[2].nullable, Some(false));
+            }
+            stmt => panic!("unexpected statement {:?}", stmt),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parser_max_identifier_length() -> Result<()> {
+        let long = "a".repeat(65);
+        let err = Error::Parse(format!("[Parser] Identifier too long: {}", long));
+        let sql = format!("create table {} (a int);", long);
+        assert_eq!(Parser::new(&sql).parse(), Err(err));
+        let sql = format!("create table t ({} int);", long);
+        assert!(Parser::new(&sql).parse().is_err());
+
+        let sql = format!("create table {0} ({0} int);", &long[1..]);
+        Parser::new(&sql).parse()?;
+        assert!(Parser::new("select * from abcd;")
+            .with_max_identifier_length(3)
+            .parse()
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parser_arithmetic() -> Result<()> {
+        use ast::{Consts, Expression, Operation};
+        let int = |i| Box::new(Expression::Consts(Consts::Integer(i)));
+        let field = |f: &str| Box::new(Expression::Field(f.to_string()));
+        let op = |o: Operation| Box::new(Expression::Operation(o));
+
+        let sql = "insert into t values (1 + 2 * 3, (1 + 2) * 3, 8 - 2 - 1);";
+        let stmt = Parser::new(sql).parse()?;
+        let values = match stmt {
+            ast::Statement::Insert { values, .. } => values,
+            stmt => panic!("unexpected statement {:?}", stmt),
+        };
+        let mul = op(Operation::Multiply(int(2), int(3)));
+        let add = op(Operation::Add(int(1), int(2)));
+        let sub = op(Operation::Subtract(int(8), int(2)));
+        assert_eq!(
+            values[0],
+            vec![
+                *op(Operation::Add(int(1), mul)),
+                *op(Operation::Multiply(add, int(3))),
+                *op(Operation::Subtract(sub, int(1))),
+            ]
+        );
+
+        let stmt = Parser::new("select * from t where a / 2 >= b - 1;").parse()?;
+        let filter = match stmt {
+            ast::Statement::Select { filter, .. } => filter,
+            stmt => panic!("unexpected statement {:?}", stmt),
+        };
+        assert_eq!(
+            filter,
+            Some(*op(Operation::GreaterThanOrEqual(
+                op(Operation::Divide(field("a"), int(2))),
+                op(Operation::Subtract(field("b"), int(1))),
+            )))
+        );
+
+        for sql in [
+            "insert into t values (1 +);",
+            "insert into t values ((1 + 2);",
+        ] {
+            assert!(Parser::new(sql).parse().is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parser_negate() -> Result<()> {
+        use ast::{Consts, Expression, Operation};
+        let neg = |e: Expression| Expression::Operation(Operation::Negate(Box::new(e)));
+        let int = |i| Box::new(Expression::Consts(Consts::Integer(i)));
+
+        let stmt = Parser::new("insert into t values (-5, -3.14, -(1+2), 2 * -a);").parse()?;
+        let values = match stmt {
+            ast::Statement::Insert { values, .. } => values,
+            stmt => panic!("unexpected statement {:?}", stmt),
+        };
+        let field = Expression::Field("a".to_string());
+        assert_eq!(
+            values[0],
+            vec![
+                neg(*int(5)),
+                neg(Consts::Float("3.14".parse()?).into()),
+                neg(Expression::Operation(Operation::Add(int(1), int(2)))),
+                Expression::Operation(Operation::Multiply(int(2), Box::new(neg(field)))),
+            ]
+        );
+
+        assert!(Parser::new("insert into t values (-);").parse().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parser_typed_literal() -> Result<()> {
+        use ast::{Consts, Expression};
+        let sql = "insert into t values (INTEGER '42', bigint '-1', float '1.5', bool 'true', \
+                   text 'a', bytes 'b');";
+        let values = match Parser::new(sql).parse()? {
+            ast::Statement::Insert { values, .. } => values,
+            stmt => panic!("unexpected statement {:?}", stmt),
+        };
+        assert_eq!(
+            values[0],
+            vec![
+                Expression::Consts(Consts::Integer(42)),
+                Expression::Consts(Consts::BigInt(-1)),
+                Expression::Consts(Consts::Float(1.5)),
+                Expression::Consts(Consts::Boolean(true)),
+                Expression::Consts(Consts::String("a".to_string())),
+                Expression::Consts(Consts::Bytes(b"b".to_vec())),
+            ]
+        );
+
+        assert_eq!(
+            Parser::new("insert into t values (INTEGER 'abc');").parse(),
+            Err(Error::Parse(
+                "[Parser] Invalid typed literal 'abc'".to_string()
+            ))
+        );
+        for sql in [
+            "insert into t values (boolean 'maybe');",
+            "insert into t values (integer 42);",
+            "insert into t values (integer);",
+        ] {
+            assert!(Parser::new(sql).parse().is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parser_relaxed_type_keyword() -> Result<()> {
+        use ast::{Consts, Expression, Operation};
+        let field = || Box::new(Expression::Field("text".to_string()));
+        let string = |s: &str| Box::new(Expression::Consts(Consts::String(s.to_string())));
+        let eq = |l, r| Box::new(Expression::Operation(Operation::Equal(l, r)));
+        let sql = "select text, text + 1, text 'a' from t where text = 'a' and text 'b' = text;";
+        assert!(Parser::new(sql).parse().is_err());
+
+        let stmt = Parser::new(sql)
+            .with_relaxed_keywords(vec![Keyword::Text])
+            .parse()?;
+        let (columns, filter) = match stmt {
+            ast::Statement::Select {
+                columns, filter, ..
+            } => (columns, filter),
+            stmt => panic!("unexpected statement {:?}", stmt),
+        };
+        assert_eq!(
+            columns,
+            Some(vec![
+                *field(),
+                Expression::Operation(Operation::Add(
+                    field(),
+                    Box::new(Expression::Consts(Consts::Integer(1)))
+                )),
+                *string("a"),
+            ])
+        );
+        assert_eq!(
+            filter,
+            Some(Expression::Operation(Operation::And(
+                eq(field(), string("a")),
+                eq(string("b"), field()),
+            )))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parser_logic() -> Result<()> {
+        use ast::{Consts, Expression, Operation};
+        let int = |i| Box::new(Expression::Consts(Consts::Integer(i)));
+        let field = |f: &str| Box::new(Expression::Field(f.to_string()));
+        let op = |o: Operation| Box::new(Expression::Operation(o));
+        let filter = |sql: &str| -> Result<Option<Expression>> {
+            match Parser::new(sql).parse()? {
+                ast::Statement::Select { filter, .. } => Ok(filter),
+                stmt => panic!("unexpected statement {:?}", stmt),
+            }
+        };
+
+        assert_eq!(
+            filter("select * from t where a = 1 and b = 2 or not c;")?,
+            Some(*op(Operation::Or(
+                op(Operation::And(
+                    op(Operation::Equal(field("a"), int(1))),
+                    op(Operation::Equal(field("b"), int(2))),
+                )),
+                op(Operation::Not(field("c"))),
+            )))
+        );
+        assert_eq!(
+            filter("select * from t where not a + 1 = 2 and b;")?,
+            Some(*op(Operation::And(
+                op(Operation::Not(op(Operation::Equal(
+                    op(Operation::Add(field("a"), int(1))),
+                    int(2),
+                )))),
+                field("b"),
+            )))
+        );
+
+        // The NOT of a column constraint after a default value is not an operator.
+        let stmt = Parser::new("create table t (a int default 1 not null);").parse()?;
+        let columns = match stmt {
+            ast::Statement::CreateTable { columns, .. } => columns,
+            stmt => panic!("unexpected statement {:?}", stmt),
+        };
+        assert_eq!(columns[0].default, Some(*int(1)));
+        assert_eq!(columns[0].nullable, Some(false));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parser_function() -> Result<()> {
+        use ast::{Consts, Expression, Operation};
+        let columns = match Parser::new("select count(*), Count(a, 1 + 2) from t;").parse()? {
+            ast::Statement::Select { columns, .. } => columns,
+            stmt => panic!("unexpected statement {:?}", stmt),
+        };
+        let int = |i| Box::new(Expression::Consts(Consts::Integer(i)));
+        assert_eq!(
+            columns,
+            Some(vec![
+                Expression::Function {
+                    name: "COUNT".to_string(),
+                    args: vec![],
+                },
+                Expression::Function {
+                    name: "COUNT".to_string(),
+                    args: vec![
+                        Expression::Field("a".to_string()),
+                        Expression::Operation(Operation::Add(int(1), int(2))),
+                    ],
+                },
+            ])
+        );
+
+        for sql in [
+            "select count( from t;",
+            "select count(*, a) from t;",
+            "select count(a,) from t;",
+            "select count() from t;",
+            "select count(*) from t group a;",
+            "select count(*) from t group by;",
+            "select count(*) from t group by a,;",
+        ] {
+            assert!(Parser::new(sql).parse().is_err(), "{}", sql);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parser_subquery() -> Result<()> {
+        use ast::{Expression, Operation, Quantifier};
+        let sql = "select * from t where a > all (select b from u);";
+        let filter = match Parser::new(sql).parse()? {
+            ast::Statement::Select { filter, .. } => filter,
+            stmt => panic!("unexpected statement {:?}", stmt),
+        };
+        let query = Parser::new("select b from u;").parse()?;
+        assert_eq!(
+            filter,
+            Some(Expression::Operation(Operation::GreaterThan(
+                Box::new(Expression::Field("a".to_string())),
+                Box::new(Expression::Subquery {
+                    quantifier: Quantifier::All,
+                    query: Box::new(query),
+                }),
+            )))
+        );
+
+        for sql in [
+            "select * from t where a > all (select b from u;",
+            "select * from t where a > any select b from u;",
+            "select * from t where a + any (select b from u);",
+            "select * from t where a = any (1);",
+            "select * from t where any (select b from u) = a;",
+        ] {
+            assert!(Parser::new(sql).parse().is_err(), "{}", sql);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_statement_to_sql() -> Result<()> {
+        for sql in [
+            "create table t1 (a int primary key, b text not null default 'it''s', c float null);",
+            "create temp table t1 (a int primary key default 1 + 2 * 3 comment 'a');",
+            r#"create table "Select" ("a b" int primary key, "x""y" bool default not true);"#,
+            "insert into t1 values (1, -2, -(3 - 4), 1.5, 2.0, null, true, x'00ff');",
+            "replace into t1 (a, b) values (1, 'x'), (2, 'y');",
+            "insert into t1 default values;",
+            "insert into t1 values (170141183460469231731687303715884105727);",
+            "select * from t1;",
+            "select a, b from t1 where a = 1 and b != 'x' or not c > -d;",
+            "select * from t1 where (a + 1) * 2 <= b / 3 order by a desc, b limit 10 offset 5;",
+            "select a from t1 where a = 1 for update;",
+            "select a * 2, -b, 'x' from t1;",
+            "select 1 + 1, 'hello';",
+            "select count(*), count(a) + 1 from t1;",
+            "select b, \"C\", count(*) from t1 where a > 1 group by b, \"C\" order by b;",
+            "select * from t1 where a > all (select b from t2 where c = 1) and not a = any (select 1);",
+            "delete from t1 where a >= 2;",
+            "update t1 set a = a - 1, b = 'y' where b < 'z';",
+            "begin;",
+            "commit;",
+            "rollback;",
+            "select integer '-1', bigint '5', bigint '-5', float '-1.5', float 'inf', \
+             float '-inf';",
+        ] {
+            let stmt = Parser::new(sql).parse()?;
+            let rendered = stmt.to_sql();
+            assert_eq!(Parser::new(&rendered).parse()?, stmt, "{}", rendered);
+        }
+
+        // NaN never equals itself, so only the rendering is compared.
+        let stmt = Parser::new("select float 'NaN';").parse()?;
+        assert_eq!(stmt.to_sql(), "SELECT FLOAT 'NaN';");
+        assert_eq!(Parser::new(&stmt.to_sql()).parse()?.to_sql(), stmt.to_sql());
+
+        let stmt = Parser::new("select a, \"B\" from t1 where not (a = -1 or b = 'x');").parse()?;
+        assert_eq!(
+            stmt.to_sql(),
+            r#"SELECT a, "B" FROM t1 WHERE NOT ((a = (-1)) OR (b = 'x'));"#
+        );
+        Ok(())
+    }
+}
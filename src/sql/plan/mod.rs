@@ -1,15 +1,16 @@
-use planner::Planner;
+pub use planner::Planner;
 
 use crate::error::Result;
 use super::{
     engine::Transaction,
-    executor::{Executor, ResultSet},
-    parser::ast::{self, Expression},
+    executor::{ExecContext, Executor, ResultSet},
+    parser::ast::{self, Expression, OrderDirection},
     schema::Table,
 };
 
 mod planner;
 
+#[derive(Clone)]
 pub enum Node {
     CreateTable {
         schema: Table,
@@ -19,21 +20,90 @@ pub enum Node {
         table_name: String,
         columns: Vec<String>,
         values: Vec<Vec<Expression>>,
+        replace: bool,
+        // Skip invalid rows and report their errors instead of failing the insert.
+        collect_errors: bool,
     },
 
     Scan {
         table_name: String,
+        // A column = constant predicate evaluated while scanning, see
+        // Transaction::scan_table_where.
+        filter: Option<(String, Expression)>,
+        // Skips unreadable rows, see Transaction::scan_table_lenient.
+        lenient: bool,
+    },
+
+    // A single row holding the values of the expressions, for SELECT without FROM.
+    ValuesScan {
+        exprs: Vec<Expression>,
+    },
+
+    Filter {
+        source: Box<Node>,
+        predicate: Expression,
+    },
+
+    // Locks the source rows, see Transaction::lock_row.
+    Lock {
+        table_name: String,
+        source: Box<Node>,
+    },
+
+    Order {
+        source: Box<Node>,
+        order_by: Vec<(String, OrderDirection)>,
+    },
+
+    Offset {
+        source: Box<Node>,
+        offset: usize,
+    },
+
+    Limit {
+        source: Box<Node>,
+        limit: usize,
+    },
+
+    // Folds the source rows of each group into one row holding the grouped columns followed
+    // by the aggregates' values, in columns named by the aggregates' SQL. Without grouped
+    // columns all rows form one group, even when there are none.
+    Aggregate {
+        source: Box<Node>,
+        group_by: Vec<String>,
+        aggregates: Vec<Expression>,
+    },
+
+    Projection {
+        source: Box<Node>,
+        exprs: Vec<Expression>,
+    },
+
+    Delete {
+        table_name: String,
+        source: Box<Node>,
+    },
+
+    Update {
+        table_name: String,
+        source: Box<Node>,
+        assignments: Vec<(String, Expression)>,
     },
 }
 
+#[derive(Clone)]
 pub struct Plan(pub Node);
 
 impl Plan {
-    pub fn build(stmt: ast::Statement) -> Self {
+    pub fn build(stmt: ast::Statement) -> Result<Self> {
         Planner::new().build(stmt)
     }
 
-    pub fn execute<T: Transaction>(self, txn: &mut T) -> Result<ResultSet> {
-        <dyn Executor<T>>::build(self.0).execute(txn)
+    pub fn execute<T: Transaction + 'static>(
+        self,
+        txn: &mut T,
+        ctx: &ExecContext,
+    ) -> Result<ResultSet> {
+        <dyn Executor<T>>::build(self.0).execute(txn, ctx)
     }
 }
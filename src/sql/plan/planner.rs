@@ -1,52 +1,303 @@
+use std::collections::HashMap;
+
 use super::{Node, Plan};
-use crate::sql::{parser::ast, schema::{self, Table}, types::Value};
+use crate::{
+    error::{Error, Result},
+    sql::{
+        parser::ast,
+        schema::{self, Table},
+        types::Value,
+    },
+};
+
+pub struct Planner {
+    collect_insert_errors: bool,
+    lenient_scan: bool,
+    tables: HashMap<String, Table>,
+}
 
-pub struct Planner;
+impl Default for Planner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl Planner {
     pub fn new() -> Self {
-        Self {}
+        Self {
+            collect_insert_errors: false,
+            lenient_scan: false,
+            tables: HashMap::new(),
+        }
+    }
+
+    // Plans inserts which skip invalid rows and report their errors, see ResultSet::Insert.
+    pub fn with_collect_insert_errors(mut self, enabled: bool) -> Self {
+        self.collect_insert_errors = enabled;
+        self
     }
 
-    pub fn build(&mut self, stmt: ast::Statement) -> Plan {
-        Plan(self.build_statement(stmt))
+    // Plans queries which skip unreadable rows, see Transaction::scan_table_lenient.
+    // Deletes and updates always fail on them.
+    pub fn with_lenient_scan(mut self, enabled: bool) -> Self {
+        self.lenient_scan = enabled;
+        self
     }
 
-    fn build_statement(&self, stmt: ast::Statement) -> Node {
-        match stmt {
-            ast::Statement::Select { table_name } => Node::Scan { table_name },
-            ast::Statement::CreateTable { name, columns } => Node::CreateTable {
-                schema: Table {
+    // Schemas the statements are checked against, e.g. for unknown columns. Statements
+    // on other tables are only checked when executed.
+    pub fn with_tables(mut self, tables: Vec<Table>) -> Self {
+        self.tables = tables.into_iter().map(|t| (t.name.clone(), t)).collect();
+        self
+    }
+
+    pub fn build(&mut self, stmt: ast::Statement) -> Result<Plan> {
+        Ok(Plan(self.build_statement(stmt)?))
+    }
+
+    fn build_statement(&self, stmt: ast::Statement) -> Result<Node> {
+        Ok(match stmt {
+            ast::Statement::Select {
+                table_name,
+                mut columns,
+                filter,
+                group_by,
+                order_by,
+                limit,
+                offset,
+                for_update,
+            } => {
+                let mut aggregates: Vec<ast::Expression> = Vec::new();
+                for expr in columns.iter().flatten().flat_map(|e| e.functions()) {
+                    if !aggregates.contains(expr) {
+                        aggregates.push(expr.clone());
+                    }
+                }
+                let grouped = !aggregates.is_empty() || !group_by.is_empty();
+                // Grouped rows only hold the grouped columns and the aggregates.
+                if grouped {
+                    let ungrouped = match &columns {
+                        Some(exprs) => exprs.iter().find_map(|e| ungrouped_column(e, &group_by)),
+                        None => Some("*"),
+                    };
+                    if let Some(column) = ungrouped {
+                        return Err(Error::Internal(format!(
+                            "column {} must be grouped by or used in an aggregate",
+                            column
+                        )));
+                    }
+                }
+                // The columns of the rows to sort, when known.
+                let mut source_columns = match &table_name {
+                    Some(table_name) => self.tables.get(table_name).map(|table| {
+                        table
+                            .columns
+                            .iter()
+                            .map(|c| c.name.clone())
+                            .collect::<Vec<_>>()
+                    }),
+                    None => columns
+                        .as_ref()
+                        .map(|exprs| exprs.iter().map(ast::Expression::column_name).collect()),
+                };
+                // The grouped columns must be columns of the table.
+                if let (Some(_), Some(columns)) = (&table_name, &source_columns) {
+                    if let Some(name) = group_by.iter().find(|name| !columns.contains(name)) {
+                        return Err(Error::Parse(format!("column {} does not exist", name)));
+                    }
+                }
+                let mut node = match table_name {
+                    Some(table_name) => {
+                        let mut node =
+                            self.build_scan(table_name.clone(), filter, self.lenient_scan);
+                        // Only the rows matching the filter are locked, before any limit.
+                        if for_update {
+                            node = Node::Lock {
+                                table_name,
+                                source: Box::new(node),
+                            };
+                        }
+                        node
+                    }
+                    // Without FROM the columns are evaluated into the only row, which
+                    // leaves nothing to project.
+                    None if !grouped => {
+                        let exprs = columns.take().unwrap_or_default();
+                        self.build_filter(Node::ValuesScan { exprs }, filter)
+                    }
+                    // Aggregates without FROM fold over a single row without columns.
+                    None => self.build_filter(Node::ValuesScan { exprs: Vec::new() }, filter),
+                };
+                if grouped {
+                    let mut columns = group_by.clone();
+                    columns.extend(aggregates.iter().map(ast::Expression::column_name));
+                    source_columns = Some(columns);
+                    node = Node::Aggregate {
+                        source: Box::new(node),
+                        group_by,
+                        aggregates,
+                    };
+                }
+                // Sorting before the projection allows ordering by columns not selected.
+                let unknown = order_by
+                    .iter()
+                    .find(|(name, _)| source_columns.as_ref().is_some_and(|c| !c.contains(name)));
+                if let Some((name, _)) = unknown {
+                    return Err(Error::Parse(format!("column {} does not exist", name)));
+                }
+                if !order_by.is_empty() {
+                    node = Node::Order {
+                        source: Box::new(node),
+                        order_by,
+                    };
+                }
+                if let Some(offset) = offset {
+                    node = Node::Offset {
+                        source: Box::new(node),
+                        offset,
+                    };
+                }
+                if let Some(limit) = limit {
+                    node = Node::Limit {
+                        source: Box::new(node),
+                        limit,
+                    };
+                }
+                match columns {
+                    Some(exprs) => Node::Projection {
+                        source: Box::new(node),
+                        exprs,
+                    },
+                    None => node,
+                }
+            }
+            ast::Statement::Delete { table_name, filter } => Node::Delete {
+                table_name: table_name.clone(),
+                source: Box::new(self.build_scan(table_name, filter, false)),
+            },
+            ast::Statement::Update {
+                table_name,
+                assignments,
+                filter,
+            } => Node::Update {
+                table_name: table_name.clone(),
+                source: Box::new(self.build_scan(table_name, filter, false)),
+                assignments,
+            },
+            ast::Statement::CreateTable { name, columns, .. } => {
+                // Identifiers are lowercased by the lexer, so this is case-insensitive.
+                for (i, col) in columns.iter().enumerate() {
+                    if columns[..i].iter().any(|c| c.name == col.name) {
+                        return Err(Error::Internal(format!("duplicate column {}", col.name)));
+                    }
+                }
+                let schema = Table {
                     name,
                     columns: columns
                         .into_iter()
                         .map(|col| {
-                            let nullable = col.nullable.unwrap_or(true);
+                            // Primary keys are implicitly NOT NULL.
+                            let nullable = match (col.primary_key, col.nullable) {
+                                (true, Some(true)) => {
+                                    return Err(Error::Internal(format!(
+                                        "primary key {} cannot be nullable",
+                                        col.name
+                                    )))
+                                }
+                                (true, _) => false,
+                                (false, nullable) => nullable.unwrap_or(true),
+                            };
                             let default = match col.default {
-                                Some(expr) => Some(Value::from_expression(expr)),
+                                Some(expr) => Some(Value::from_expression(expr)?),
                                 None if nullable => Some(Value::Null),
                                 None => None,
                             };
 
-                            schema::Column {
+                            Ok(schema::Column {
                                 name: col.name.clone(),
                                 datatype: col.datatype,
                                 nullable,
                                 default,
-                            }
+                                comment: col.comment,
+                                primary_key: col.primary_key,
+                            })
                         })
-                        .collect(),
-                },
-            },
+                        .collect::<Result<_>>()?,
+                };
+                schema.validate()?;
+                Node::CreateTable { schema }
+            }
             ast::Statement::Insert {
                 table_name,
                 columns,
                 values,
+                replace,
             } => Node::Insert {
                 table_name,
                 columns: columns.unwrap_or_default(),
                 values,
+                replace,
+                collect_errors: self.collect_insert_errors,
             },
+            // Transactions are controlled by the session, they have nothing to plan.
+            stmt @ (ast::Statement::Begin | ast::Statement::Commit | ast::Statement::Rollback) => {
+                return Err(Error::Internal(format!("cannot plan {:?}", stmt)))
+            }
+        })
+    }
+
+    fn build_scan(
+        &self,
+        table_name: String,
+        filter: Option<ast::Expression>,
+        lenient: bool,
+    ) -> Node {
+        // A single equality between a column and a constant is pushed into the scan,
+        // unless unreadable rows are to be skipped.
+        if let (Some(ast::Expression::Operation(ast::Operation::Equal(l, r))), false) =
+            (&filter, lenient)
+        {
+            match (l.as_ref(), r.as_ref()) {
+                (ast::Expression::Field(column), value @ ast::Expression::Consts(_))
+                | (value @ ast::Expression::Consts(_), ast::Expression::Field(column)) => {
+                    return Node::Scan {
+                        table_name,
+                        filter: Some((column.clone(), value.clone())),
+                        lenient,
+                    };
+                }
+                _ => {}
+            }
         }
+        self.build_filter(
+            Node::Scan {
+                table_name,
+                filter: None,
+                lenient,
+            },
+            filter,
+        )
+    }
+
+    fn build_filter(&self, source: Node, filter: Option<ast::Expression>) -> Node {
+        match filter {
+            Some(predicate) => Node::Filter {
+                source: Box::new(source),
+                predicate,
+            },
+            None => source,
+        }
+    }
+}
+
+// A column the expression reads outside of the aggregates which isn't grouped by.
+fn ungrouped_column<'a>(expr: &'a ast::Expression, group_by: &[String]) -> Option<&'a str> {
+    match expr {
+        ast::Expression::Field(name) if !group_by.contains(name) => Some(name),
+        ast::Expression::Operation(operation) => operation
+            .operands()
+            .into_iter()
+            .find_map(|expr| ungrouped_column(expr, group_by)),
+        _ => None,
     }
 }
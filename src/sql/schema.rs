@@ -1,17 +1,111 @@
 use serde::{Deserialize, Serialize};
 
-use super::types::{DataType, Value};
+use super::types::{DataType, Row, Value};
+use crate::error::{Error, Result};
 
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Table { 
     pub name: String,
     pub columns: Vec<Column>
 }
 
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Column {
     pub name: String,
     pub datatype: DataType,
     pub nullable: bool,
-    pub default: Option<Value>
-}
\ No newline at end of file
+    pub default: Option<Value>,
+    pub comment: Option<String>,
+    pub primary_key: bool,
+}
+
+impl Table {
+    // Checks that the table has exactly one primary key column, which is not nullable.
+    pub fn validate(&self) -> Result<()> {
+        if self.columns.is_empty() {
+            return Err(Error::Internal(format!(
+                "table {} has no columns",
+                self.name
+            )));
+        }
+        match self.columns.iter().filter(|c| c.primary_key).count() {
+            1 => {}
+            0 => {
+                return Err(Error::Internal(format!(
+                    "table {} has no primary key",
+                    self.name
+                )))
+            }
+            _ => {
+                return Err(Error::Internal(format!(
+                    "table {} has multiple primary keys",
+                    self.name
+                )))
+            }
+        }
+        let pk = &self.columns[self.primary_key_index()?];
+        if pk.nullable {
+            return Err(Error::Internal(format!(
+                "primary key {} cannot be nullable",
+                pk.name
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn primary_key_index(&self) -> Result<usize> {
+        self.columns
+            .iter()
+            .position(|c| c.primary_key)
+            .ok_or(Error::Internal(format!(
+                "table {} has no primary key",
+                self.name
+            )))
+    }
+
+    // Returns the primary key value of a row of this table.
+    pub fn get_row_key(&self, row: &Row) -> Result<Value> {
+        let idx = self.primary_key_index()?;
+        row.get(idx).cloned().ok_or(Error::Internal(format!(
+            "row has no primary key column {}",
+            self.columns[idx].name
+        )))
+    }
+
+    pub fn coerce_row(&self, row: Row) -> Row {
+        row.into_iter()
+            .enumerate()
+            .map(|(i, v)| match self.columns.get(i) {
+                Some(col) => v.coerce_to(&col.datatype),
+                None => v,
+            })
+            .collect()
+    }
+
+    // Checks that the row has a value for every column, and every value against the
+    // nullability and type of its column.
+    pub fn validate_row(&self, row: &Row) -> Result<()> {
+        if row.len() != self.columns.len() {
+            return Err(Error::Internal("row/schema arity mismatch".to_string()));
+        }
+        for (i, col) in self.columns.iter().enumerate() {
+            match row[i].datatype() {
+                None if col.nullable => {}
+                None => {
+                    return Err(Error::Internal(format!(
+                        "column {} cannot be null",
+                        col.name
+                    )))
+                }
+                Some(dt) if dt != col.datatype => {
+                    return Err(Error::Internal(format!(
+                        "column {} type mismatch",
+                        col.name
+                    )))
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
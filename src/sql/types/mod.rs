@@ -1,44 +1,833 @@
+use std::{
+    cmp::Ordering,
+    fmt::Display,
+    hash::{Hash, Hasher},
+};
+
 use serde::{Deserialize, Serialize};
 
-use super::parser::ast::{Consts, Expression};
+use super::parser::ast::{Consts, Expression, Operation};
+use crate::error::{Error, Result};
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum DataType {
     Boolean,
     Integer,
+    BigInt,
     Float,
     String,
+    Bytes,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Value {
     Null,
     Boolean(bool),
     Integer(i64),
+    BigInt(i128),
     Float(f64),
     String(String),
+    Bytes(Vec<u8>),
+}
+
+// How `/` divides two integers. Truncate, the default, keeps an integer rounded toward
+// zero, so 7 / 2 is 3. Float divides them as floats, so 7 / 2 is 3.5.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum IntegerDivision {
+    #[default]
+    Truncate,
+    Float,
 }
 
 impl Value {
-    pub fn from_expression(expr: Expression) -> Self {
-        match expr {
+    pub fn from_expression(expr: Expression) -> Result<Self> {
+        Ok(match expr {
             Expression::Consts(Consts::Null) => Self::Null,
             Expression::Consts(Consts::Boolean(b)) => Self::Boolean(b),
             Expression::Consts(Consts::Integer(i)) => Self::Integer(i),
+            Expression::Consts(Consts::BigInt(i)) => Self::BigInt(i),
             Expression::Consts(Consts::Float(f)) => Self::Float(f),
             Expression::Consts(Consts::String(s)) => Self::String(s),
+            Expression::Consts(Consts::Bytes(b)) => Self::Bytes(b),
+            // Operations on constants fold into a constant.
+            Expression::Operation(operation) => {
+                return Self::evaluate_operation(&operation, IntegerDivision::default(), &|e| {
+                    Self::from_expression(e.clone())
+                })
+            }
+            expr => {
+                return Err(Error::Internal(format!(
+                    "expression {:?} is not a constant",
+                    expr
+                )))
+            }
+        })
+    }
+
+    // Applies the operation to its operands, which are evaluated with the given function.
+    pub fn evaluate_operation(
+        operation: &Operation,
+        division: IntegerDivision,
+        evaluate: &dyn Fn(&Expression) -> Result<Value>,
+    ) -> Result<Value> {
+        let (left, right) = match operation {
+            Operation::Negate(expr) => return evaluate(expr)?.negate(),
+            Operation::Not(expr) => {
+                return match evaluate(expr)? {
+                    Self::Null => Ok(Self::Null),
+                    Self::Boolean(b) => Ok(Self::Boolean(!b)),
+                    v => Err(Error::Internal(format!("cannot apply NOT to {:?}", v))),
+                }
+            }
+            Operation::And(l, r)
+            | Operation::Or(l, r)
+            | Operation::Equal(l, r)
+            | Operation::NotEqual(l, r)
+            | Operation::LessThan(l, r)
+            | Operation::LessThanOrEqual(l, r)
+            | Operation::GreaterThan(l, r)
+            | Operation::GreaterThanOrEqual(l, r)
+            | Operation::Add(l, r)
+            | Operation::Subtract(l, r)
+            | Operation::Multiply(l, r)
+            | Operation::Divide(l, r) => (evaluate(l)?, evaluate(r)?),
+        };
+        if let Operation::And(..) | Operation::Or(..) = operation {
+            return Self::logical(operation, &left, &right);
+        }
+        // Any operation on NULL is NULL, comparing with it is neither true nor false.
+        if left == Self::Null || right == Self::Null {
+            return Ok(Self::Null);
+        }
+
+        match operation {
+            Operation::Add(..) => left.arithmetic(&right, i128::checked_add, |l, r| l + r),
+            Operation::Subtract(..) => left.arithmetic(&right, i128::checked_sub, |l, r| l - r),
+            Operation::Multiply(..) => left.arithmetic(&right, i128::checked_mul, |l, r| l * r),
+            Operation::Divide(..) if right.to_f64() == Some(0.0) => {
+                Err(Error::Internal("division by zero".to_string()))
+            }
+            Operation::Divide(..) => match (division, left.to_f64(), right.to_f64()) {
+                (IntegerDivision::Float, Some(l), Some(r)) => Ok(Self::Float(l / r)),
+                _ => left.arithmetic(&right, i128::checked_div, |l, r| l / r),
+            },
+            operation => {
+                let ordering = left.compare(&right)?;
+                Ok(Self::Boolean(match operation {
+                    Operation::Equal(..) => ordering.is_eq(),
+                    Operation::NotEqual(..) => ordering.is_ne(),
+                    Operation::LessThan(..) => ordering.is_lt(),
+                    Operation::LessThanOrEqual(..) => ordering.is_le(),
+                    Operation::GreaterThan(..) => ordering.is_gt(),
+                    _ => ordering.is_ge(),
+                }))
+            }
         }
     }
 
+    // Three-valued AND and OR, where NULL is an unknown boolean: FALSE AND NULL is FALSE
+    // and TRUE OR NULL is TRUE, since the result doesn't depend on the unknown operand.
+    fn logical(operation: &Operation, left: &Value, right: &Value) -> Result<Value> {
+        let boolean = |v: &Value| match v {
+            Self::Null => Ok(None),
+            Self::Boolean(b) => Ok(Some(*b)),
+            v => Err(Error::Internal(format!(
+                "cannot apply boolean logic to {:?}",
+                v
+            ))),
+        };
+        let (l, r) = (boolean(left)?, boolean(right)?);
+        // The operand value which decides the result on its own.
+        let decisive = matches!(operation, Operation::Or(..));
+        Ok(if l == Some(decisive) || r == Some(decisive) {
+            Self::Boolean(decisive)
+        } else if l.is_none() || r.is_none() {
+            Self::Null
+        } else {
+            Self::Boolean(!decisive)
+        })
+    }
+
+    fn negate(&self) -> Result<Value> {
+        let overflow = || Error::Internal(format!("integer overflow in -{}", self));
+        Ok(match self {
+            Self::Null => Self::Null,
+            Self::Integer(i) => Self::Integer(i.checked_neg().ok_or_else(overflow)?),
+            Self::BigInt(i) => Self::BigInt(i.checked_neg().ok_or_else(overflow)?),
+            Self::Float(f) => Self::Float(-f),
+            v => return Err(Error::Internal(format!("cannot negate {:?}", v))),
+        })
+    }
+
+    // Adds two non-null numbers as the + operator does.
+    pub fn add(&self, other: &Value) -> Result<Value> {
+        self.arithmetic(other, i128::checked_add, |l, r| l + r)
+    }
+
+    // Integers stay integers, widened to BigInt if either side is one, while mixing in a
+    // float gives a float. Integer division truncates.
+    fn arithmetic(
+        &self,
+        other: &Value,
+        int_op: fn(i128, i128) -> Option<i128>,
+        float_op: fn(f64, f64) -> f64,
+    ) -> Result<Value> {
+        let overflow = || Error::Internal(format!("integer overflow in {} and {}", self, other));
+        match (self, other) {
+            (Self::Float(_), _) | (_, Self::Float(_)) => {
+                if let (Some(l), Some(r)) = (self.to_f64(), other.to_f64()) {
+                    return Ok(Self::Float(float_op(l, r)));
+                }
+            }
+            (Self::Integer(l), Self::Integer(r)) => {
+                let result = int_op(*l as i128, *r as i128).ok_or_else(overflow)?;
+                return Ok(Self::Integer(
+                    i64::try_from(result).map_err(|_| overflow())?,
+                ));
+            }
+            (l, r) => {
+                if let (Ok(Some(l)), Ok(Some(r))) = (l.as_i128(), r.as_i128()) {
+                    return Ok(Self::BigInt(int_op(l, r).ok_or_else(overflow)?));
+                }
+            }
+        }
+        Err(Error::Internal(format!(
+            "cannot apply arithmetic to {:?} and {:?}",
+            self, other
+        )))
+    }
+
     pub fn datatype(&self) -> Option<DataType> {
         match self {
             Self::Null => None,
             Self::Boolean(_) => Some(DataType::Boolean),
             Self::Float(_) => Some(DataType::Float),
             Self::Integer(_) => Some(DataType::Integer),
-            Self::String(_) => Some(DataType::String)
+            Self::BigInt(_) => Some(DataType::BigInt),
+            Self::String(_) => Some(DataType::String),
+            Self::Bytes(_) => Some(DataType::Bytes),
+        }
+    }
+
+    // Widens the value to the column type where that is lossless, e.g. Integer to BigInt.
+    pub fn coerce_to(self, datatype: &DataType) -> Self {
+        match (self, datatype) {
+            (Self::Integer(i), DataType::BigInt) => Self::BigInt(i as i128),
+            (value, _) => value,
+        }
+    }
+
+    // Converts the value to the datatype, parsing strings into the other types, e.g. for
+    // typed literals such as INTEGER '42'. Errors when the value doesn't convert.
+    // Option::is_none_or would need Rust 1.82.
+    #[allow(clippy::unnecessary_map_or)]
+    pub fn try_coerce_to(self, datatype: &DataType) -> Result<Self> {
+        let invalid =
+            |value: &str| Error::Internal(format!("cannot coerce '{}' to {:?}", value, datatype));
+        Ok(match (self, datatype) {
+            (Self::String(s), DataType::Boolean) => match s.to_lowercase().as_str() {
+                "true" => Self::Boolean(true),
+                "false" => Self::Boolean(false),
+                _ => return Err(invalid(&s)),
+            },
+            (Self::String(s), DataType::Integer) => {
+                Self::Integer(s.trim().parse().map_err(|_| invalid(&s))?)
+            }
+            (Self::String(s), DataType::BigInt) => {
+                Self::BigInt(s.trim().parse().map_err(|_| invalid(&s))?)
+            }
+            (Self::String(s), DataType::Float) => {
+                Self::Float(s.trim().parse().map_err(|_| invalid(&s))?)
+            }
+            (Self::String(s), DataType::Bytes) => Self::Bytes(s.into_bytes()),
+            (value, datatype) => match value.coerce_to(datatype) {
+                value if value.datatype().map_or(true, |t| &t == datatype) => value,
+                value => return Err(invalid(&value.to_string())),
+            },
+        })
+    }
+
+    // Orders two non-null values. Integer, BigInt and Float values compare by their
+    // numeric value, any other mix of types cannot be compared.
+    pub fn compare(&self, other: &Value) -> Result<Ordering> {
+        let ordering = match (self, other) {
+            (Self::Boolean(l), Self::Boolean(r)) => l.partial_cmp(r),
+            (Self::String(l), Self::String(r)) => l.partial_cmp(r),
+            (Self::Bytes(l), Self::Bytes(r)) => l.partial_cmp(r),
+            (Self::Float(_), _) | (_, Self::Float(_)) => match (self.to_f64(), other.to_f64()) {
+                (Some(l), Some(r)) => l.partial_cmp(&r),
+                _ => None,
+            },
+            (l, r) => match (l.as_i128(), r.as_i128()) {
+                (Ok(Some(l)), Ok(Some(r))) => l.partial_cmp(&r),
+                _ => None,
+            },
+        };
+        ordering.ok_or(Error::Internal(format!(
+            "cannot compare {:?} and {:?}",
+            self, other
+        )))
+    }
+
+    // Total order used for sorting: NULL first, then booleans, numbers, strings and bytes.
+    // Numbers of any type compare by value, with NaN after every other number.
+    pub fn sort_cmp(&self, other: &Value) -> Ordering {
+        let rank = |v: &Value| match v {
+            Self::Null => 0,
+            Self::Boolean(_) => 1,
+            Self::Integer(_) | Self::BigInt(_) | Self::Float(_) => 2,
+            Self::String(_) => 3,
+            Self::Bytes(_) => 4,
+        };
+        match rank(self).cmp(&rank(other)) {
+            Ordering::Equal => self.compare(other).unwrap_or_else(|_| {
+                // Only NULLs and NaN are left, each equal to its own kind.
+                let nan = |v: &Value| matches!(v, Self::Float(f) if f.is_nan());
+                nan(self).cmp(&nan(other))
+            }),
+            ordering => ordering,
+        }
+    }
+
+    // SQL equality: NULL if either side is NULL, otherwise a boolean.
+    pub fn logical_eq(&self, other: &Value) -> Result<Value> {
+        if *self == Self::Null || *other == Self::Null {
+            return Ok(Self::Null);
+        }
+        Ok(Self::Boolean(self.compare(other)?.is_eq()))
+    }
+
+    // The numeric value as a float, None for non-numeric values.
+    pub fn to_f64(&self) -> Option<f64> {
+        match self {
+            Self::Integer(i) => Some(*i as f64),
+            Self::BigInt(i) => Some(*i as f64),
+            Self::Float(f) => Some(*f),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Result<Option<i64>> {
+        match self {
+            Self::Null => Ok(None),
+            Self::Integer(i) => Ok(Some(*i)),
+            v => Err(Error::Internal(format!("value {:?} is not an integer", v))),
+        }
+    }
+
+    pub fn as_i128(&self) -> Result<Option<i128>> {
+        match self {
+            Self::Null => Ok(None),
+            Self::Integer(i) => Ok(Some(*i as i128)),
+            Self::BigInt(i) => Ok(Some(*i)),
+            v => Err(Error::Internal(format!("value {:?} is not an integer", v))),
+        }
+    }
+
+    pub fn as_f64(&self) -> Result<Option<f64>> {
+        match self {
+            Self::Null => Ok(None),
+            Self::Float(f) => Ok(Some(*f)),
+            v => Err(Error::Internal(format!("value {:?} is not a float", v))),
+        }
+    }
+
+    pub fn as_bool(&self) -> Result<Option<bool>> {
+        match self {
+            Self::Null => Ok(None),
+            Self::Boolean(b) => Ok(Some(*b)),
+            v => Err(Error::Internal(format!("value {:?} is not a boolean", v))),
+        }
+    }
+
+    pub fn as_str(&self) -> Result<Option<&str>> {
+        match self {
+            Self::Null => Ok(None),
+            Self::String(s) => Ok(Some(s)),
+            v => Err(Error::Internal(format!("value {:?} is not a string", v))),
+        }
+    }
+
+    pub fn as_bytes(&self) -> Result<Option<&[u8]>> {
+        match self {
+            Self::Null => Ok(None),
+            Self::Bytes(b) => Ok(Some(b)),
+            v => Err(Error::Internal(format!("value {:?} is not bytes", v))),
+        }
+    }
+}
+
+// Values are equal when they are of the same type and hold the same value, so that they can
+// key hash maps, e.g. for grouping. Unlike with f64, a NaN float equals any other NaN, and
+// 0.0 equals -0.0. NULL only equals NULL, which the = operator doesn't do.
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Null, Self::Null) => true,
+            (Self::Boolean(l), Self::Boolean(r)) => l == r,
+            (Self::Integer(l), Self::Integer(r)) => l == r,
+            (Self::BigInt(l), Self::BigInt(r)) => l == r,
+            (Self::Float(l), Self::Float(r)) => l == r || (l.is_nan() && r.is_nan()),
+            (Self::String(l), Self::String(r)) => l == r,
+            (Self::Bytes(l), Self::Bytes(r)) => l == r,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Value {}
+
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Self::Null => {}
+            Self::Boolean(b) => b.hash(state),
+            Self::Integer(i) => i.hash(state),
+            Self::BigInt(i) => i.hash(state),
+            // Floats hash by their bits, with the equal NaNs and zeros hashing the same.
+            Self::Float(f) if f.is_nan() => f64::NAN.to_bits().hash(state),
+            Self::Float(f) if *f == 0.0 => 0.0f64.to_bits().hash(state),
+            Self::Float(f) => f.to_bits().hash(state),
+            Self::String(s) => s.hash(state),
+            Self::Bytes(b) => b.hash(state),
+        }
+    }
+}
+
+impl From<Value> for Consts {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Null => Self::Null,
+            Value::Boolean(b) => Self::Boolean(b),
+            Value::Integer(i) => Self::Integer(i),
+            Value::BigInt(i) => Self::BigInt(i),
+            Value::Float(f) => Self::Float(f),
+            Value::String(s) => Self::String(s),
+            Value::Bytes(b) => Self::Bytes(b),
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Null => write!(f, "NULL"),
+            Self::Boolean(true) => write!(f, "TRUE"),
+            Self::Boolean(false) => write!(f, "FALSE"),
+            Self::Integer(i) => write!(f, "{}", i),
+            Self::BigInt(i) => write!(f, "{}", i),
+            // Whole numbers keep a decimal point, e.g. 1.0, and floats never use exponents.
+            Self::Float(v) if v.is_finite() && v.fract() == 0.0 => write!(f, "{:.1}", v),
+            Self::Float(v) => write!(f, "{}", v),
+            Self::String(s) => write!(f, "{}", s),
+            Self::Bytes(b) => {
+                write!(f, "x'")?;
+                for byte in b {
+                    write!(f, "{:02X}", byte)?;
+                }
+                write!(f, "'")
+            }
         }
     }
 }
 
 pub type Row = Vec<Value>;
+
+// Typed accessors over a row, returning None for Null and an error on a type mismatch.
+pub trait RowExt {
+    fn value(&self, idx: usize) -> Result<&Value>;
+
+    // Looks up a value by column name, using the column names of the result set.
+    fn by_name(&self, columns: &[String], name: &str) -> Result<&Value> {
+        let idx = columns
+            .iter()
+            .position(|c| c == name)
+            .ok_or(Error::Internal(format!("column {} does not exist", name)))?;
+        self.value(idx)
+    }
+
+    fn get_i64(&self, idx: usize) -> Result<Option<i64>> {
+        self.value(idx)?.as_i64()
+    }
+
+    fn get_f64(&self, idx: usize) -> Result<Option<f64>> {
+        self.value(idx)?.as_f64()
+    }
+
+    fn get_bool(&self, idx: usize) -> Result<Option<bool>> {
+        self.value(idx)?.as_bool()
+    }
+
+    fn get_str(&self, idx: usize) -> Result<Option<&str>> {
+        self.value(idx)?.as_str()
+    }
+
+    fn get_bytes(&self, idx: usize) -> Result<Option<&[u8]>> {
+        self.value(idx)?.as_bytes()
+    }
+}
+
+impl RowExt for Row {
+    fn value(&self, idx: usize) -> Result<&Value> {
+        self.get(idx).ok_or(Error::Internal(format!(
+            "column index {} out of range",
+            idx
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cmp::Ordering,
+        collections::{hash_map::DefaultHasher, HashMap},
+        hash::{Hash, Hasher},
+    };
+
+    use super::{DataType, IntegerDivision, RowExt, Value};
+    use crate::{
+        error::{Error, Result},
+        sql::parser::ast::{Consts, Expression, Operation},
+    };
+
+    #[test]
+    fn test_row_accessors() -> Result<()> {
+        let columns = vec![
+            "a".to_string(),
+            "b".to_string(),
+            "c".to_string(),
+            "d".to_string(),
+        ];
+        let row = vec![
+            Value::Integer(1),
+            Value::String("foo".to_string()),
+            Value::Null,
+            Value::Float(1.5),
+        ];
+
+        assert_eq!(row.get_i64(0)?, Some(1));
+        assert_eq!(row.get_str(1)?, Some("foo"));
+        assert_eq!(row.get_bool(2)?, None);
+        assert_eq!(row.get_f64(3)?, Some(1.5));
+        assert_eq!(row.by_name(&columns, "b")?.as_str()?, Some("foo"));
+
+        assert!(row.get_str(0).is_err());
+        assert!(row.get_i64(3).is_err());
+        assert!(row.get_i64(4).is_err());
+        assert!(row.by_name(&columns, "e").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_value_try_coerce() -> Result<()> {
+        let coerce = |s: &str, datatype| Value::String(s.to_string()).try_coerce_to(&datatype);
+        assert_eq!(coerce("42", DataType::Integer)?, Value::Integer(42));
+        assert_eq!(coerce(" -7 ", DataType::BigInt)?, Value::BigInt(-7));
+        assert_eq!(coerce("1.5", DataType::Float)?, Value::Float(1.5));
+        assert_eq!(coerce("TRUE", DataType::Boolean)?, Value::Boolean(true));
+        assert_eq!(coerce("ab", DataType::Bytes)?, Value::Bytes(b"ab".to_vec()));
+        assert_eq!(
+            coerce("ab", DataType::String)?,
+            Value::String("ab".to_string())
+        );
+        assert_eq!(
+            Value::Integer(1).try_coerce_to(&DataType::BigInt)?,
+            Value::BigInt(1)
+        );
+        assert_eq!(Value::Null.try_coerce_to(&DataType::Integer)?, Value::Null);
+
+        for (s, datatype) in [
+            ("abc", DataType::Integer),
+            ("1.5", DataType::Integer),
+            ("99999999999999999999", DataType::Integer),
+            ("yes", DataType::Boolean),
+            ("x", DataType::Float),
+        ] {
+            assert!(matches!(coerce(s, datatype), Err(Error::Internal(_))));
+        }
+        assert!(Value::Integer(1).try_coerce_to(&DataType::Boolean).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_value_hash() {
+        let hash = |value: &Value| {
+            let mut hasher = DefaultHasher::new();
+            value.hash(&mut hasher);
+            hasher.finish()
+        };
+        for (l, r) in [
+            (Value::Null, Value::Null),
+            (Value::Integer(1), Value::Integer(1)),
+            (Value::Float(1.5), Value::Float(1.5)),
+            (Value::Float(f64::NAN), Value::Float(-f64::NAN)),
+            (Value::Float(0.0), Value::Float(-0.0)),
+            (
+                Value::String("a".to_string()),
+                Value::String("a".to_string()),
+            ),
+        ] {
+            assert_eq!(l, r);
+            assert_eq!(hash(&l), hash(&r), "{:?} {:?}", l, r);
+        }
+        for (l, r) in [
+            (Value::Null, Value::Integer(0)),
+            (Value::Null, Value::Boolean(false)),
+            (Value::Integer(1), Value::BigInt(1)),
+            (Value::Integer(1), Value::Float(1.0)),
+            (Value::Float(f64::NAN), Value::Float(1.0)),
+            (Value::String("a".to_string()), Value::Bytes(b"a".to_vec())),
+        ] {
+            assert_ne!(l, r);
+        }
+
+        // Equal rows land in the same bucket.
+        let mut groups: HashMap<Vec<Value>, usize> = HashMap::new();
+        for row in [
+            vec![Value::String("a".to_string()), Value::Null],
+            vec![Value::Float(f64::NAN), Value::Integer(1)],
+            vec![Value::String("a".to_string()), Value::Null],
+            vec![Value::Float(f64::NAN), Value::Integer(1)],
+            vec![Value::Float(f64::NAN), Value::Integer(2)],
+        ] {
+            *groups.entry(row).or_default() += 1;
+        }
+        assert_eq!(groups.len(), 3);
+        assert_eq!(
+            groups[&vec![Value::String("a".to_string()), Value::Null]],
+            2
+        );
+        assert_eq!(groups[&vec![Value::Float(f64::NAN), Value::Integer(1)]], 2);
+    }
+
+    #[test]
+    fn test_value_display() {
+        let cases = vec![
+            (Value::Null, "NULL"),
+            (Value::Boolean(true), "TRUE"),
+            (Value::Boolean(false), "FALSE"),
+            (Value::Integer(-42), "-42"),
+            (Value::BigInt(-(1 << 70)), "-1180591620717411303424"),
+            (Value::Float(1.0), "1.0"),
+            (Value::Float(-0.0), "-0.0"),
+            (Value::Float(2.5), "2.5"),
+            (Value::Float(1e20), "100000000000000000000.0"),
+            (Value::Float(1e-7), "0.0000001"),
+            (Value::Float(f64::NAN), "NaN"),
+            (Value::Float(f64::INFINITY), "inf"),
+            (Value::String("it's 'a'".to_string()), "it's 'a'"),
+            (Value::String("".to_string()), ""),
+            (Value::Bytes(vec![0, 171, 255]), "x'00ABFF'"),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(value.to_string(), expected, "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn test_value_sort_cmp() {
+        // Values in ascending order, grouping the ones which sort as equal.
+        let groups = vec![
+            vec![Value::Null],
+            vec![Value::Boolean(false)],
+            vec![Value::Boolean(true)],
+            vec![Value::Float(f64::NEG_INFINITY)],
+            vec![Value::Integer(-1), Value::BigInt(-1), Value::Float(-1.0)],
+            vec![Value::Float(-0.5)],
+            vec![Value::Integer(0), Value::BigInt(0), Value::Float(-0.0)],
+            vec![Value::Integer(2), Value::BigInt(2), Value::Float(2.0)],
+            vec![Value::BigInt(i128::MAX)],
+            vec![Value::Float(f64::INFINITY)],
+            vec![Value::Float(f64::NAN)],
+            vec![Value::String("".to_string())],
+            vec![Value::String("A".to_string())],
+            vec![Value::String("a".to_string())],
+            vec![Value::String("ab".to_string())],
+            vec![Value::Bytes(vec![])],
+            vec![Value::Bytes(vec![0])],
+        ];
+
+        for (i, left_group) in groups.iter().enumerate() {
+            for (j, right_group) in groups.iter().enumerate() {
+                for left in left_group {
+                    for right in right_group {
+                        assert_eq!(
+                            left.sort_cmp(right),
+                            i.cmp(&j),
+                            "{:?} <=> {:?}",
+                            left,
+                            right
+                        );
+                    }
+                }
+            }
+        }
+
+        let mut values = vec![
+            Value::String("b".to_string()),
+            Value::Integer(3),
+            Value::Null,
+            Value::Float(1.5),
+            Value::Boolean(true),
+        ];
+        values.sort_by(|a, b| a.sort_cmp(b));
+        assert_eq!(
+            values,
+            vec![
+                Value::Null,
+                Value::Boolean(true),
+                Value::Float(1.5),
+                Value::Integer(3),
+                Value::String("b".to_string()),
+            ]
+        );
+        assert_eq!(Value::Null.sort_cmp(&Value::Null), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_value_logical_eq() -> Result<()> {
+        let (t, f, null) = (Value::Boolean(true), Value::Boolean(false), Value::Null);
+        let s = |v: &str| Value::String(v.to_string());
+        let cases = vec![
+            (Value::Null, Value::Null, null.clone()),
+            (Value::Null, Value::Integer(1), null.clone()),
+            (Value::Boolean(false), Value::Null, null.clone()),
+            (s("a"), Value::Null, null.clone()),
+            (Value::Boolean(true), Value::Boolean(true), t.clone()),
+            (Value::Boolean(true), Value::Boolean(false), f.clone()),
+            (Value::Integer(1), Value::Integer(1), t.clone()),
+            (Value::Integer(1), Value::BigInt(1), t.clone()),
+            (Value::Integer(1), Value::Float(1.0), t.clone()),
+            (Value::Integer(1), Value::Float(1.5), f.clone()),
+            (Value::Float(0.0), Value::Float(-0.0), t.clone()),
+            (s("a"), s("a"), t.clone()),
+            (s("a"), s("A"), f.clone()),
+            (Value::Bytes(vec![1]), Value::Bytes(vec![1]), t.clone()),
+        ];
+        for (left, right, expected) in cases {
+            assert_eq!(left.logical_eq(&right)?, expected, "{:?}", left);
+            assert_eq!(right.logical_eq(&left)?, expected, "{:?}", right);
+        }
+
+        let nan = Value::Float(f64::NAN);
+        assert!(nan.logical_eq(&nan).is_err());
+        assert!(Value::Integer(1).logical_eq(&s("1")).is_err());
+        assert!(Value::Boolean(true).logical_eq(&Value::Integer(1)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_value_logic() -> Result<()> {
+        type Op = fn(Box<Expression>, Box<Expression>) -> Operation;
+        let eval = |operation: Op, l, r| {
+            let consts = |v| Box::new(Expression::Consts(v));
+            Value::from_expression(Expression::Operation(operation(consts(l), consts(r))))
+        };
+        let (t, f, n) = (Consts::Boolean(true), Consts::Boolean(false), Consts::Null);
+
+        let cases: Vec<(Op, _, _, _)> = vec![
+            (Operation::And, t.clone(), t.clone(), Value::Boolean(true)),
+            (Operation::And, t.clone(), f.clone(), Value::Boolean(false)),
+            (Operation::And, n.clone(), f.clone(), Value::Boolean(false)),
+            (Operation::And, t.clone(), n.clone(), Value::Null),
+            (Operation::And, n.clone(), n.clone(), Value::Null),
+            (Operation::Or, f.clone(), f.clone(), Value::Boolean(false)),
+            (Operation::Or, f.clone(), t.clone(), Value::Boolean(true)),
+            (Operation::Or, n.clone(), t.clone(), Value::Boolean(true)),
+            (Operation::Or, f.clone(), n.clone(), Value::Null),
+        ];
+        for (operation, l, r, expected) in cases {
+            let msg = format!("{:?} {:?}", l, r);
+            assert_eq!(eval(operation, l, r)?, expected, "{}", msg);
+        }
+        assert!(eval(Operation::And, t.clone(), Consts::Integer(1)).is_err());
+        assert!(eval(Operation::Or, Consts::String("a".to_string()), n.clone()).is_err());
+
+        let not = |v| {
+            let operation = Operation::Not(Box::new(Expression::Consts(v)));
+            Value::from_expression(Expression::Operation(operation))
+        };
+        assert_eq!(not(t)?, Value::Boolean(false));
+        assert_eq!(not(f)?, Value::Boolean(true));
+        assert_eq!(not(n)?, Value::Null);
+        assert!(not(Consts::Integer(0)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_value_arithmetic() -> Result<()> {
+        type Op = fn(Box<Expression>, Box<Expression>) -> Operation;
+        let eval = |operation: Op, l, r| {
+            let consts = |v| Box::new(Expression::Consts(v));
+            Value::from_expression(Expression::Operation(operation(consts(l), consts(r))))
+        };
+        let (i, b, f) = (Consts::Integer, Consts::BigInt, Consts::Float);
+
+        let cases: Vec<(Op, _, _, _)> = vec![
+            (Operation::Add, i(1), i(2), Value::Integer(3)),
+            (Operation::Subtract, i(1), b(3), Value::BigInt(-2)),
+            (Operation::Multiply, i(2), f(1.5), Value::Float(3.0)),
+            (Operation::Divide, i(7), i(2), Value::Integer(3)),
+            (Operation::Divide, i(-7), i(2), Value::Integer(-3)),
+            (Operation::Divide, f(7.0), i(2), Value::Float(3.5)),
+            (Operation::Add, Consts::Null, i(1), Value::Null),
+            (Operation::Divide, Consts::Null, i(0), Value::Null),
+            (Operation::Add, i(i64::MAX), b(1), Value::BigInt(1 << 63)),
+        ];
+        for (operation, l, r, expected) in cases {
+            let msg = format!("{:?} {:?}", l, r);
+            assert_eq!(eval(operation, l, r)?, expected, "{}", msg);
+        }
+
+        let division_by_zero = Err(Error::Internal("division by zero".to_string()));
+        assert_eq!(eval(Operation::Divide, i(1), i(0)), division_by_zero);
+        assert_eq!(eval(Operation::Divide, f(1.0), f(-0.0)), division_by_zero);
+        assert!(eval(Operation::Add, i(i64::MAX), i(1)).is_err());
+        assert!(eval(Operation::Multiply, b(i128::MAX), i(2)).is_err());
+        assert!(eval(Operation::Add, i(1), Consts::String("1".to_string())).is_err());
+        assert!(eval(Operation::Add, Consts::Boolean(true), Consts::Boolean(true)).is_err());
+
+        let negate = |v| {
+            let operation = Operation::Negate(Box::new(Expression::Consts(v)));
+            Value::from_expression(Expression::Operation(operation))
+        };
+        assert_eq!(negate(i(5))?, Value::Integer(-5));
+        assert_eq!(negate(b(-5))?, Value::BigInt(5));
+        assert_eq!(negate(f(2.5))?, Value::Float(-2.5));
+        assert_eq!(negate(Consts::Null)?, Value::Null);
+        assert!(negate(i(i64::MIN)).is_err());
+        assert!(negate(Consts::String("a".to_string())).is_err());
+        assert!(negate(Consts::Boolean(true)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_value_integer_division() -> Result<()> {
+        let divide = |division, l, r| {
+            let consts = |v| Box::new(Expression::Consts(v));
+            let operation = Operation::Divide(consts(l), consts(r));
+            Value::evaluate_operation(&operation, division, &|e| Value::from_expression(e.clone()))
+        };
+        let (i, f) = (Consts::Integer, Consts::Float);
+
+        assert_eq!(
+            divide(IntegerDivision::Truncate, i(7), i(2))?,
+            Value::Integer(3)
+        );
+        assert_eq!(
+            divide(IntegerDivision::Float, i(7), i(2))?,
+            Value::Float(3.5)
+        );
+        assert_eq!(
+            divide(IntegerDivision::Float, i(-7), f(2.0))?,
+            Value::Float(-3.5)
+        );
+        assert_eq!(
+            divide(IntegerDivision::Float, Consts::Null, i(2))?,
+            Value::Null
+        );
+        assert!(divide(IntegerDivision::Float, i(1), i(0)).is_err());
+        Ok(())
+    }
+}
@@ -3,7 +3,7 @@ use fs4::fs_std::FileExt;
 use super::engine::{Engine, EngineIterator};
 use crate::error::Result;
 use std::{
-    collections::{btree_map, BTreeMap},
+    collections::{btree_map, BTreeMap, VecDeque},
     fs::{File, OpenOptions},
     io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write},
     path::PathBuf,
@@ -11,17 +11,31 @@ use std::{
 
 pub type KeyDir = BTreeMap<Vec<u8>, (u64, u32)>;
 
+const DEFAULT_SCAN_BATCH_SIZE: usize = 64;
+
 pub struct DiskEngine {
     keydir: KeyDir,
     log: Log,
+    scan_batch_size: usize,
 }
 
 impl DiskEngine {
     pub fn new(file_path: PathBuf) -> Result<Self> {
         let mut log = Log::new(file_path)?;
         let keydir = log.build_keydir()?;
-        Ok(Self { log, keydir })
+        Ok(Self {
+            log,
+            keydir,
+            scan_batch_size: DEFAULT_SCAN_BATCH_SIZE,
+        })
+    }
+
+    // Number of values a scan reads from the log file at once.
+    pub fn with_scan_batch_size(mut self, batch_size: usize) -> Self {
+        self.scan_batch_size = batch_size.max(1);
+        self
     }
+
     pub fn new_compact(file_path: PathBuf) -> Result<Self> {
         let mut eng = Self::new(file_path)?;
         eng.compact()?;
@@ -35,7 +49,7 @@ impl DiskEngine {
         let mut new_keydir = KeyDir::new();
 
         for (key, (offset, val_size)) in self.keydir.iter() {
-            let value = self.log.read_value(*offset, *val_size)?;
+            let value = self.log.read_value(*offset, *val_size as u64)?;
             let (new_offset, new_size) = new_log.write_entry(key, Some(&value))?;
             new_keydir.insert(key.clone(), (new_offset + new_size as u64 - *val_size as u64, *val_size));
         }
@@ -62,7 +76,7 @@ impl Engine for DiskEngine {
     fn get(&mut self, key: Vec<u8>) -> Result<Option<Vec<u8>>> {
         match self.keydir.get(&key) {
             Some((offset, val_size)) => {
-                let val = self.log.read_value(*offset, *val_size)?;
+                let val = self.log.read_value(*offset, *val_size as u64)?;
                 Ok(Some(val))
             }
             None => Ok(None),
@@ -79,6 +93,9 @@ impl Engine for DiskEngine {
         DiskEngineIterator {
             inner: self.keydir.range(range),
             log: &mut self.log,
+            batch_size: self.scan_batch_size,
+            front: VecDeque::new(),
+            back: VecDeque::new(),
         }
     }
 }
@@ -86,13 +103,80 @@ impl Engine for DiskEngine {
 pub struct DiskEngineIterator<'a> {
     inner: btree_map::Range<'a, Vec<u8>, (u64, u32)>,
     log: &'a mut Log,
+    batch_size: usize,
+    front: VecDeque<(Vec<u8>, Vec<u8>)>,
+    back: VecDeque<(Vec<u8>, Vec<u8>)>,
 }
 
 impl<'a> DiskEngineIterator<'a> {
-    fn map(&mut self, item: (&Vec<u8>, &(u64, u32))) -> <Self as Iterator>::Item {
-        let (k, (offset, val_size)) = item;
-        let value = self.log.read_value(*offset, *val_size)?;
-        Ok((k.clone(), value))
+    // Reads the values of a batch of entries, merging entries that sit next to each other in
+    // the log into a single read.
+    fn read_batch(
+        &mut self,
+        entries: &[(&Vec<u8>, &(u64, u32))],
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let mut by_offset = (0..entries.len()).collect::<Vec<_>>();
+        by_offset.sort_by_key(|&i| entries[i].1 .0);
+
+        let mut values = vec![Vec::new(); entries.len()];
+        let mut run_start = 0;
+        while run_start < by_offset.len() {
+            let mut run_end = run_start + 1;
+            while run_end < by_offset.len() {
+                let (_, (prev_offset, prev_size)) = entries[by_offset[run_end - 1]];
+                let (key, (offset, _)) = entries[by_offset[run_end]];
+                let next =
+                    prev_offset + *prev_size as u64 + LOG_HEADER_SIZE as u64 + key.len() as u64;
+                if *offset != next {
+                    break;
+                }
+                run_end += 1;
+            }
+
+            let (_, (start, _)) = entries[by_offset[run_start]];
+            let (_, (last_offset, last_size)) = entries[by_offset[run_end - 1]];
+            let span = self
+                .log
+                .read_value(*start, last_offset + *last_size as u64 - start)?;
+            for &i in &by_offset[run_start..run_end] {
+                let (_, (offset, val_size)) = entries[i];
+                let from = (offset - start) as usize;
+                values[i] = span[from..from + *val_size as usize].to_vec();
+            }
+            run_start = run_end;
+        }
+
+        Ok(entries
+            .iter()
+            .map(|(k, _)| (*k).clone())
+            .zip(values)
+            .collect())
+    }
+
+    fn fill_front(&mut self) -> Result<()> {
+        let entries = self
+            .inner
+            .by_ref()
+            .take(self.batch_size)
+            .collect::<Vec<_>>();
+        if !entries.is_empty() {
+            self.front = self.read_batch(&entries)?.into();
+        }
+        Ok(())
+    }
+
+    fn fill_back(&mut self) -> Result<()> {
+        let mut entries = self
+            .inner
+            .by_ref()
+            .rev()
+            .take(self.batch_size)
+            .collect::<Vec<_>>();
+        if !entries.is_empty() {
+            entries.reverse();
+            self.back = self.read_batch(&entries)?.into();
+        }
+        Ok(())
     }
 }
 
@@ -102,13 +186,29 @@ impl<'a> Iterator for DiskEngineIterator<'a> {
     type Item = Result<(Vec<u8>, Vec<u8>)>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.inner.next().map(|item| self.map(item))
+        if self.front.is_empty() {
+            if let Err(err) = self.fill_front() {
+                return Some(Err(err));
+            }
+        }
+        self.front
+            .pop_front()
+            .or_else(|| self.back.pop_front())
+            .map(Ok)
     }
 }
 
 impl<'a> DoubleEndedIterator for DiskEngineIterator<'a> {
     fn next_back(&mut self) -> Option<Self::Item> {
-        self.inner.next_back().map(|item| self.map(item))
+        if self.back.is_empty() {
+            if let Err(err) = self.fill_back() {
+                return Some(Err(err));
+            }
+        }
+        self.back
+            .pop_back()
+            .or_else(|| self.front.pop_back())
+            .map(Ok)
     }
 }
 
@@ -179,9 +279,9 @@ impl Log {
         Ok((offset, total_size))
     }
 
-    fn read_value(&mut self, offset: u64, val_size: u32) -> Result<Vec<u8>> {
+    fn read_value(&mut self, offset: u64, len: u64) -> Result<Vec<u8>> {
         self.file.seek(SeekFrom::Start(offset))?;
-        let mut buf = vec![0; val_size as usize];
+        let mut buf = vec![0; len as usize];
         self.file.read_exact(&mut buf)?;
         Ok(buf)
     }
@@ -202,3 +302,92 @@ impl Log {
         Ok((key, val_size))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::DiskEngine;
+    use crate::{error::Result, storage::engine::Engine};
+
+    #[test]
+    fn test_disk_scan_batch_size() -> Result<()> {
+        let dir = std::env::temp_dir().join("sqldb-rs-test-disk-scan-batch");
+        let path = dir.join("sqldb-log");
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir)?;
+        }
+
+        let mut eng = DiskEngine::new(path.clone())?.with_scan_batch_size(1);
+        for i in 0..1000u32 {
+            eng.set(i.to_be_bytes().to_vec(), i.to_string().into_bytes())?;
+        }
+        for i in (0..1000u32).step_by(3) {
+            eng.set(i.to_be_bytes().to_vec(), format!("new-{}", i).into_bytes())?;
+        }
+        for i in (0..1000u32).step_by(7) {
+            eng.delete(i.to_be_bytes().to_vec())?;
+        }
+        let expected = eng.scan(..).collect::<Result<Vec<_>>>()?;
+        assert_eq!(expected.len(), 1000 - 143);
+        drop(eng);
+
+        for batch_size in [2, 7, 64, 5000] {
+            let mut eng = DiskEngine::new(path.clone())?.with_scan_batch_size(batch_size);
+            assert_eq!(eng.scan(..).collect::<Result<Vec<_>>>()?, expected);
+
+            let mut reversed = eng.scan(..).rev().collect::<Result<Vec<_>>>()?;
+            reversed.reverse();
+            assert_eq!(reversed, expected);
+
+            // Alternating ends must meet in the middle without repeating or skipping entries.
+            let mut iter = eng.scan(..);
+            let (mut front, mut back) = (Vec::new(), Vec::new());
+            while let Some(item) = iter.next().transpose()? {
+                front.push(item);
+                match iter.next_back().transpose()? {
+                    Some(item) => back.push(item),
+                    None => break,
+                }
+            }
+            back.reverse();
+            front.extend(back);
+            assert_eq!(front, expected);
+        }
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_disk_reopen() -> Result<()> {
+        let dir = std::env::temp_dir().join("sqldb-rs-test-disk-reopen");
+        let path = dir.join("sqldb-log");
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir)?;
+        }
+
+        let mut eng = DiskEngine::new(path.clone())?;
+        eng.set(b"a".to_vec(), b"1".to_vec())?;
+        eng.set(b"b".to_vec(), b"2".to_vec())?;
+        eng.set(b"c".to_vec(), vec![])?;
+        eng.set(b"a".to_vec(), b"3".to_vec())?;
+        eng.delete(b"b".to_vec())?;
+        drop(eng);
+
+        let mut eng = DiskEngine::new(path.clone())?;
+        assert_eq!(eng.get(b"a".to_vec())?, Some(b"3".to_vec()));
+        assert_eq!(eng.get(b"b".to_vec())?, None);
+        assert_eq!(eng.get(b"c".to_vec())?, Some(vec![]));
+        assert_eq!(
+            eng.scan_prefix(vec![]).collect::<Result<Vec<_>>>()?,
+            vec![(b"a".to_vec(), b"3".to_vec()), (b"c".to_vec(), vec![])]
+        );
+        drop(eng);
+
+        let mut eng = DiskEngine::new_compact(path)?;
+        assert_eq!(eng.get(b"a".to_vec())?, Some(b"3".to_vec()));
+        assert_eq!(eng.get(b"b".to_vec())?, None);
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -1,4 +1,4 @@
-use std::ops::RangeBounds;
+use std::ops::{Bound, RangeBounds};
 
 use crate::error::Result;
 
@@ -15,8 +15,19 @@ pub trait Engine {
 
     fn scan(&mut self, range: impl RangeBounds<Vec<u8>>) -> Self::EngineIterator<'_>;
 
+    // Scans the keys starting with the prefix. The exclusive end bound is the prefix with
+    // its last non-0xff byte incremented and anything after it dropped, e.g. [1, 0xff]
+    // ends at [2]. An empty or all-0xff prefix has no end bound.
     fn scan_prefix(&mut self, prefix: Vec<u8>) -> Self::EngineIterator<'_> {
-        todo!()
+        let end = match prefix.iter().rposition(|b| *b != 0xff) {
+            Some(i) => {
+                let mut end = prefix[..=i].to_vec();
+                end[i] += 1;
+                Bound::Excluded(end)
+            }
+            None => Bound::Unbounded,
+        };
+        self.scan((Bound::Included(prefix), end))
     }
 }
 
@@ -25,7 +36,10 @@ pub trait EngineIterator: DoubleEndedIterator<Item = Result<(Vec<u8>, Vec<u8>)>>
 #[cfg(test)]
 mod tests {
     use super::Engine;
-    use crate::error::Result;
+    use crate::{
+        error::Result,
+        storage::{disk::DiskEngine, memory::MemoryEngine},
+    };
 
     fn test_point_opt(mut eng: impl Engine) -> Result<()> {
         assert_eq!(eng.get(b"not exist".to_vec())?, None);
@@ -45,4 +59,50 @@ mod tests {
 
         Ok(())
     }
+
+    fn test_scan_prefix(mut eng: impl Engine) -> Result<()> {
+        let keys: Vec<Vec<u8>> = vec![
+            vec![],
+            vec![0x00],
+            vec![0x01],
+            vec![0x01, 0x00],
+            vec![0x01, 0xff],
+            vec![0x01, 0xff, 0xff],
+            vec![0x02],
+            vec![0xff],
+            vec![0xff, 0x00],
+            vec![0xff, 0xff],
+        ];
+        for key in keys.iter().rev() {
+            eng.set(key.clone(), key.clone())?;
+        }
+
+        let mut scan = |prefix: Vec<u8>| -> Result<Vec<Vec<u8>>> {
+            eng.scan_prefix(prefix).map(|r| r.map(|(k, _)| k)).collect()
+        };
+        assert_eq!(scan(vec![])?, keys);
+        assert_eq!(scan(vec![0x01])?, keys[2..6].to_vec());
+        assert_eq!(scan(vec![0x01, 0xff])?, keys[4..6].to_vec());
+        assert_eq!(scan(vec![0x02])?, keys[6..7].to_vec());
+        assert_eq!(scan(vec![0x03])?, Vec::<Vec<u8>>::new());
+        assert_eq!(scan(vec![0xff])?, keys[7..].to_vec());
+        assert_eq!(scan(vec![0xff, 0xff])?, keys[9..].to_vec());
+        Ok(())
+    }
+
+    #[test]
+    fn test_memory_scan_prefix() -> Result<()> {
+        test_scan_prefix(MemoryEngine::new())
+    }
+
+    #[test]
+    fn test_disk_scan_prefix() -> Result<()> {
+        let dir = std::env::temp_dir().join("sqldb-rs-test-disk-scan-prefix");
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir)?;
+        }
+        test_scan_prefix(DiskEngine::new(dir.join("sqldb-log"))?)?;
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
 }
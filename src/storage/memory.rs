@@ -1,6 +1,6 @@
 use super::engine::{Engine, EngineIterator};
 use crate::error::Result;
-use std::{collections::{btree_map, BTreeMap}, ops::Bound};
+use std::collections::{btree_map, BTreeMap};
 
 pub struct MemoryEngine {
     data: BTreeMap<Vec<u8>, Vec<u8>>,
@@ -37,17 +37,6 @@ impl Engine for MemoryEngine {
             inner: self.data.range(range),
         }
     }
-
-    fn scan_prefix(&mut self, prefix: Vec<u8>) -> Self::EngineIterator<'_> {
-        let start = Bound::Included(prefix.clone());
-        let mut bound_prefix = prefix.clone();
-        if let Some(last) = bound_prefix.iter_mut().last() {
-            *last += 1;
-        }
-
-        let end = Bound::Excluded(bound_prefix);
-        self.scan((start, end))
-    }
 }
 
 pub struct MemoryEngineIterator<'a> {
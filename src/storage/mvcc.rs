@@ -1,16 +1,29 @@
-use std::sync::{Arc, Mutex};
+use std::{
+    cell::{Cell, RefCell},
+    collections::{BTreeMap, HashSet},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+};
 
 use super::engine::Engine;
-use crate::error::Result;
+use crate::error::{Error, Result};
+
+pub type Version = u64;
 
 pub struct Mvcc<E: Engine> {
     engine: Arc<Mutex<E>>,
+    recover_poisoned: bool,
+    recovered: Arc<AtomicBool>,
 }
 
 impl<E: Engine> Clone for Mvcc<E> {
     fn clone(&self) -> Self {
         Mvcc {
             engine: self.engine.clone(),
+            recover_poisoned: self.recover_poisoned,
+            recovered: self.recovered.clone(),
         }
     }
 }
@@ -19,53 +32,680 @@ impl<E: Engine> Mvcc<E> {
     pub fn new(eng: E) -> Self {
         Mvcc {
             engine: Arc::new(Mutex::new(eng)),
+            recover_poisoned: false,
+            recovered: Arc::new(AtomicBool::new(false)),
         }
     }
 
+    // Keeps using the engine after a thread panicked while holding its lock, instead
+    // of failing every later operation. Only safe for engines whose writes are atomic.
+    pub fn with_poison_recovery(mut self) -> Self {
+        self.recover_poisoned = true;
+        self
+    }
+
     pub fn begin(&self) -> Result<MvccTransaction<E>> {
-        Ok(MvccTransaction::begin(self.engine.clone()))
+        if !self.recovered.load(Ordering::SeqCst) {
+            self.recover()?;
+        }
+        MvccTransaction::begin(self.engine.clone(), self.recover_poisoned)
+    }
+
+    // Returns the current value of the counter, see MvccTransaction::sequence. Reads it
+    // without beginning a transaction.
+    pub fn sequence(&self, key: Vec<u8>) -> Result<u64> {
+        let mut engine = MvccTransaction::lock_engine(&self.engine, self.recover_poisoned)?;
+        read_sequence(&mut *engine, key)
+    }
+
+    // Rolls back the transactions that were still active when the engine was last
+    // closed, e.g. by a crash. Runs before the first transaction begins, when none of
+    // them can still be running.
+    fn recover(&self) -> Result<()> {
+        let mut engine = MvccTransaction::lock_engine(&self.engine, self.recover_poisoned)?;
+        if self.recovered.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        let mut versions = Vec::new();
+        let mut iter = engine.scan_prefix(MvccKeyPrefix::TxnActive.encode());
+        while let Some((key, _)) = iter.next().transpose()? {
+            match MvccKey::decode(&key)? {
+                MvccKey::TxnActive(version) => versions.push(version),
+                key => return Err(Error::Internal(format!("[Mvcc] unexpected key {:?}", key))),
+            }
+        }
+        drop(iter);
+
+        for version in versions {
+            rollback_version(&mut *engine, version)?;
+        }
+        self.recovered.store(true, Ordering::SeqCst);
+        Ok(())
     }
 }
 
+// A transaction sees the versions committed before it began plus its own writes. A
+// transaction which is dropped without committing is rolled back.
 pub struct MvccTransaction<E: Engine> {
     engine: Arc<Mutex<E>>,
+    recover_poisoned: bool,
+    state: TransactionState,
+    finished: Cell<bool>,
+    // Adjustments of counters by key, applied when the transaction commits.
+    counters: RefCell<BTreeMap<Vec<u8>, i64>>,
+}
+
+struct TransactionState {
+    version: Version,
+    // Transactions still running when this one began. Their writes stay invisible.
+    active: HashSet<Version>,
+}
+
+impl TransactionState {
+    fn is_visible(&self, version: Version) -> bool {
+        version <= self.version && !self.active.contains(&version)
+    }
 }
 
 impl<E: Engine> MvccTransaction<E> {
-    pub fn begin(eng: Arc<Mutex<E>>) -> Self {
-        Self { engine: eng }
+    pub fn begin(eng: Arc<Mutex<E>>, recover_poisoned: bool) -> Result<Self> {
+        let mut engine = Self::lock_engine(&eng, recover_poisoned)?;
+        let version = match engine.get(MvccKey::NextVersion.encode())? {
+            Some(v) => bincode::deserialize(&v)?,
+            None => 1,
+        };
+        engine.set(
+            MvccKey::NextVersion.encode(),
+            bincode::serialize(&(version + 1))?,
+        )?;
+
+        let mut active = HashSet::new();
+        let mut iter = engine.scan_prefix(MvccKeyPrefix::TxnActive.encode());
+        while let Some((key, _)) = iter.next().transpose()? {
+            match MvccKey::decode(&key)? {
+                MvccKey::TxnActive(version) => active.insert(version),
+                key => return Err(Error::Internal(format!("[Mvcc] unexpected key {:?}", key))),
+            };
+        }
+        drop(iter);
+        engine.set(MvccKey::TxnActive(version).encode(), vec![])?;
+        drop(engine);
+
+        Ok(Self {
+            engine: eng,
+            recover_poisoned,
+            state: TransactionState { version, active },
+            finished: Cell::new(false),
+            counters: RefCell::new(BTreeMap::new()),
+        })
+    }
+
+    pub fn version(&self) -> Version {
+        self.state.version
     }
 
+    fn lock_engine(engine: &Mutex<E>, recover_poisoned: bool) -> Result<MutexGuard<'_, E>> {
+        match engine.lock() {
+            Ok(engine) => Ok(engine),
+            Err(err) if recover_poisoned => {
+                engine.clear_poison();
+                Ok(err.into_inner())
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, E>> {
+        Self::lock_engine(&self.engine, self.recover_poisoned)
+    }
+
+    // Makes the writes visible to transactions which begin afterwards.
     pub fn commit(&self) -> Result<()> {
+        let mut engine = self.lock()?;
+        let mut keys = Vec::new();
+        let mut iter = engine.scan_prefix(MvccKeyPrefix::TxnWrite(self.state.version).encode());
+        while let Some((key, _)) = iter.next().transpose()? {
+            keys.push(key);
+        }
+        drop(iter);
+
+        for key in keys {
+            engine.delete(key)?;
+        }
+        for (key, delta) in self.counters.take() {
+            let value = read_sequence(&mut *engine, key.clone())?.saturating_add_signed(delta);
+            engine.set(MvccKey::Sequence(key).encode(), bincode::serialize(&value)?)?;
+        }
+        engine.delete(MvccKey::TxnActive(self.state.version).encode())?;
+        self.finished.set(true);
         Ok(())
     }
 
+    // Removes every version written by the transaction.
     pub fn rollback(&self) -> Result<()> {
+        let mut engine = self.lock()?;
+        rollback_version(&mut *engine, self.state.version)?;
+        self.finished.set(true);
         Ok(())
     }
 
+    // Increments the counter under the given key and returns its new value. The counter
+    // is kept outside the versioned keys: every transaction sees each increment at once
+    // and a rollback keeps it, so concurrent increments never conflict.
+    pub fn next_sequence(&self, key: Vec<u8>) -> Result<u64> {
+        let mut engine = self.lock()?;
+        let key = MvccKey::Sequence(key).encode();
+        let next = match engine.get(key.clone())? {
+            Some(v) => bincode::deserialize::<u64>(&v)? + 1,
+            None => 1,
+        };
+        engine.set(key, bincode::serialize(&next)?)?;
+        Ok(next)
+    }
+
+    // Returns the current value of the counter, 0 if it was never incremented.
+    pub fn sequence(&self, key: Vec<u8>) -> Result<u64> {
+        let mut engine = self.lock()?;
+        read_sequence(&mut *engine, key)
+    }
+
+    pub fn set_sequence(&self, key: Vec<u8>, value: u64) -> Result<()> {
+        let mut engine = self.lock()?;
+        engine.set(MvccKey::Sequence(key).encode(), bincode::serialize(&value)?)
+    }
+
+    // Adds the delta to the counter under the given key when the transaction commits,
+    // so a rollback discards it. Shares the keys of the sequences, and like them never
+    // conflicts with concurrent adjustments.
+    pub fn adjust_counter(&self, key: Vec<u8>, delta: i64) {
+        *self.counters.borrow_mut().entry(key).or_default() += delta;
+    }
+
+    // Returns the committed value of the counter plus the transaction's own adjustments.
+    // Unlike versioned keys it includes transactions committed after this one began.
+    pub fn counter(&self, key: Vec<u8>) -> Result<u64> {
+        let delta = self.counters.borrow().get(&key).copied().unwrap_or(0);
+        Ok(self.sequence(key)?.saturating_add_signed(delta))
+    }
+
     pub fn set(&self, key: Vec<u8>, value: Vec<u8>) -> Result<()> {
-        let mut engine = self.engine.lock()?;
-        engine.set(key, value)
+        self.write(key, Some(value))
+    }
+
+    pub fn delete(&self, key: Vec<u8>) -> Result<()> {
+        self.write(key, None)
+    }
+
+    // Writes a new version of the key, or a tombstone for None.
+    fn write(&self, key: Vec<u8>, value: Option<Vec<u8>>) -> Result<()> {
+        let mut engine = self.lock()?;
+
+        // The latest version must be visible to us, otherwise a concurrent transaction
+        // wrote the key and this write would silently overwrite its change.
+        let from = MvccKey::Version(key.clone(), 0).encode();
+        let to = MvccKey::Version(key.clone(), Version::MAX).encode();
+        if let Some((latest, _)) = engine.scan(from..=to).next_back().transpose()? {
+            match MvccKey::decode(&latest)? {
+                MvccKey::Version(_, version) if !self.state.is_visible(version) => {
+                    return Err(Error::WriteConflict)
+                }
+                _ => {}
+            }
+        }
+
+        engine.set(
+            MvccKey::TxnWrite(self.state.version, key.clone()).encode(),
+            vec![],
+        )?;
+        engine.set(
+            MvccKey::Version(key, self.state.version).encode(),
+            bincode::serialize(&value)?,
+        )
     }
 
     pub fn get(&self, key: Vec<u8>) -> Result<Option<Vec<u8>>> {
-        let mut engine = self.engine.lock()?;
-        engine.get(key)
+        let mut engine = self.lock()?;
+        let from = MvccKey::Version(key.clone(), 0).encode();
+        let to = MvccKey::Version(key, Version::MAX).encode();
+        let mut iter = engine.scan(from..=to).rev();
+        while let Some((key, value)) = iter.next().transpose()? {
+            match MvccKey::decode(&key)? {
+                MvccKey::Version(_, version) if self.state.is_visible(version) => {
+                    return Ok(bincode::deserialize(&value)?)
+                }
+                _ => {}
+            }
+        }
+        Ok(None)
     }
 
+    // Collects all matches while holding the engine lock, so the results are a
+    // point-in-time view that writes made after the scan cannot affect.
     pub fn scan_prefix(&self, prefix: Vec<u8>) -> Result<Vec<ScanResult>> {
-        let mut engine = self.engine.lock()?;
-        let mut iter = engine.scan_prefix(prefix);
-        let mut results = Vec::new();
+        let mut engine = self.lock()?;
+        let mut latest: BTreeMap<Vec<u8>, Option<Vec<u8>>> = BTreeMap::new();
+        let mut iter = engine.scan_prefix(MvccKeyPrefix::Version(prefix).encode());
         while let Some((key, value)) = iter.next().transpose()? {
-            results.push(ScanResult { key, value });
+            match MvccKey::decode(&key)? {
+                MvccKey::Version(key, version) => {
+                    // Versions of a key come in ascending order, so the last visible one wins.
+                    if self.state.is_visible(version) {
+                        latest.insert(key, bincode::deserialize(&value)?);
+                    }
+                }
+                key => return Err(Error::Internal(format!("[Mvcc] unexpected key {:?}", key))),
+            }
+        }
+
+        Ok(latest
+            .into_iter()
+            .filter_map(|(key, value)| value.map(|value| ScanResult { key, value }))
+            .collect())
+    }
+}
+
+impl<E: Engine> Drop for MvccTransaction<E> {
+    fn drop(&mut self) {
+        if !self.finished.get() {
+            let _ = self.rollback();
         }
-        Ok(results)
     }
 }
 
+fn read_sequence<E: Engine>(engine: &mut E, key: Vec<u8>) -> Result<u64> {
+    match engine.get(MvccKey::Sequence(key).encode())? {
+        Some(v) => Ok(bincode::deserialize(&v)?),
+        None => Ok(0),
+    }
+}
+
+// Deletes the versions written by the transaction along with its bookkeeping keys.
+fn rollback_version<E: Engine>(engine: &mut E, version: Version) -> Result<()> {
+    let mut keys = Vec::new();
+    let mut iter = engine.scan_prefix(MvccKeyPrefix::TxnWrite(version).encode());
+    while let Some((key, _)) = iter.next().transpose()? {
+        match MvccKey::decode(&key)? {
+            MvccKey::TxnWrite(version, written) => {
+                keys.push(MvccKey::Version(written, version).encode())
+            }
+            key => return Err(Error::Internal(format!("[Mvcc] unexpected key {:?}", key))),
+        }
+        keys.push(key);
+    }
+    drop(iter);
+
+    for key in keys {
+        engine.delete(key)?;
+    }
+    engine.delete(MvccKey::TxnActive(version).encode())
+}
+
 pub struct ScanResult {
     pub key: Vec<u8>,
     pub value: Vec<u8>,
 }
+
+// Keys are encoded so that their byte order matches the order of the variants and
+// fields, which lets a scan range over all versions of a key or key prefix.
+#[derive(Debug, PartialEq)]
+enum MvccKey {
+    NextVersion,
+    TxnActive(Version),
+    // Marks a key written by the transaction, so a rollback can find its versions.
+    TxnWrite(Version, Vec<u8>),
+    Version(Vec<u8>, Version),
+    // An unversioned counter, see MvccTransaction::next_sequence.
+    Sequence(Vec<u8>),
+}
+
+impl MvccKey {
+    fn encode(&self) -> Vec<u8> {
+        match self {
+            MvccKey::NextVersion => vec![0],
+            MvccKey::TxnActive(version) => [&[1][..], &version.to_be_bytes()].concat(),
+            MvccKey::TxnWrite(version, key) => {
+                [&[2][..], &version.to_be_bytes(), &encode_bytes(key)].concat()
+            }
+            MvccKey::Version(key, version) => {
+                [&[3][..], &encode_bytes(key), &version.to_be_bytes()].concat()
+            }
+            MvccKey::Sequence(key) => [&[4][..], &encode_bytes(key)].concat(),
+        }
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self> {
+        let invalid = || Error::Internal(format!("[Mvcc] invalid key {:?}", bytes));
+        let version = |bytes: &[u8]| -> Result<Version> {
+            Ok(Version::from_be_bytes(
+                bytes.try_into().map_err(|_| invalid())?,
+            ))
+        };
+        match bytes.split_first().ok_or_else(invalid)? {
+            (0, []) => Ok(MvccKey::NextVersion),
+            (1, rest) => Ok(MvccKey::TxnActive(version(rest)?)),
+            (2, rest) if rest.len() >= 8 => {
+                let (key, tail) = decode_bytes(&rest[8..]).ok_or_else(invalid)?;
+                if !tail.is_empty() {
+                    return Err(invalid());
+                }
+                Ok(MvccKey::TxnWrite(version(&rest[..8])?, key))
+            }
+            (3, rest) => {
+                let (key, tail) = decode_bytes(rest).ok_or_else(invalid)?;
+                Ok(MvccKey::Version(key, version(tail)?))
+            }
+            (4, rest) => match decode_bytes(rest).ok_or_else(invalid)? {
+                (key, []) => Ok(MvccKey::Sequence(key)),
+                _ => Err(invalid()),
+            },
+            _ => Err(invalid()),
+        }
+    }
+}
+
+enum MvccKeyPrefix {
+    TxnActive,
+    TxnWrite(Version),
+    // Versions of every key starting with the given bytes.
+    Version(Vec<u8>),
+}
+
+impl MvccKeyPrefix {
+    fn encode(&self) -> Vec<u8> {
+        match self {
+            MvccKeyPrefix::TxnActive => vec![1],
+            MvccKeyPrefix::TxnWrite(version) => [&[2][..], &version.to_be_bytes()].concat(),
+            MvccKeyPrefix::Version(prefix) => {
+                // Without the terminator, so that longer keys match too.
+                let mut bytes = [&[3][..], &encode_bytes(prefix)].concat();
+                bytes.truncate(bytes.len() - 2);
+                bytes
+            }
+        }
+    }
+}
+
+// Escapes 0x00 as 0x00 0xff and terminates with 0x00 0x00, which keeps the byte order
+// of the encoded keys and ensures no encoded key is a prefix of another.
+fn encode_bytes(bytes: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(bytes.len() + 2);
+    for b in bytes {
+        match b {
+            0x00 => encoded.extend([0x00, 0xff]),
+            b => encoded.push(*b),
+        }
+    }
+    encoded.extend([0x00, 0x00]);
+    encoded
+}
+
+// Decodes bytes written by encode_bytes, returning them with the remaining input.
+fn decode_bytes(bytes: &[u8]) -> Option<(Vec<u8>, &[u8])> {
+    let mut decoded = Vec::new();
+    let mut i = 0;
+    loop {
+        match (bytes.get(i)?, bytes.get(i + 1)) {
+            (0x00, Some(0x00)) => return Some((decoded, &bytes[i + 2..])),
+            (0x00, Some(0xff)) => {
+                decoded.push(0x00);
+                i += 2;
+            }
+            (0x00, _) => return None,
+            (b, _) => {
+                decoded.push(*b);
+                i += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::{decode_bytes, encode_bytes, Mvcc, MvccKey};
+    use crate::{
+        error::{Error, Result},
+        storage::{disk::DiskEngine, memory::MemoryEngine},
+    };
+
+    #[test]
+    fn test_scan_prefix_snapshot() -> Result<()> {
+        let mvcc = Mvcc::new(MemoryEngine::new());
+        let txn1 = mvcc.begin()?;
+        txn1.set(b"aa".to_vec(), b"1".to_vec())?;
+        txn1.set(b"ab".to_vec(), b"2".to_vec())?;
+
+        let results = txn1.scan_prefix(b"a".to_vec())?;
+        let txn2 = mvcc.begin()?;
+        txn2.set(b"ac".to_vec(), b"3".to_vec())?;
+
+        assert_eq!(
+            results.iter().map(|r| r.key.clone()).collect::<Vec<_>>(),
+            vec![b"aa".to_vec(), b"ab".to_vec()]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_poison_recovery() -> Result<()> {
+        let poison = |mvcc: &Mvcc<MemoryEngine>| {
+            let engine = mvcc.engine.clone();
+            let _ = thread::spawn(move || {
+                let _guard = engine.lock().unwrap();
+                panic!("poison the engine lock");
+            })
+            .join();
+        };
+
+        let mvcc = Mvcc::new(MemoryEngine::new());
+        let txn = mvcc.begin()?;
+        txn.set(b"a".to_vec(), b"1".to_vec())?;
+        txn.commit()?;
+        poison(&mvcc);
+        assert!(mvcc.begin().is_err());
+
+        let mvcc = Mvcc::new(MemoryEngine::new()).with_poison_recovery();
+        let txn = mvcc.begin()?;
+        txn.set(b"a".to_vec(), b"1".to_vec())?;
+        txn.commit()?;
+        poison(&mvcc);
+        let other = mvcc.clone();
+        thread::spawn(move || -> Result<()> {
+            let txn = other.begin()?;
+            assert_eq!(txn.get(b"a".to_vec())?, Some(b"1".to_vec()));
+            txn.set(b"b".to_vec(), b"2".to_vec())?;
+            txn.commit()
+        })
+        .join()
+        .unwrap()?;
+        assert_eq!(mvcc.begin()?.get(b"b".to_vec())?, Some(b"2".to_vec()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_key_encoding() -> Result<()> {
+        let keys = vec![
+            MvccKey::NextVersion,
+            MvccKey::TxnActive(1),
+            MvccKey::TxnActive(256),
+            MvccKey::TxnWrite(1, vec![]),
+            MvccKey::TxnWrite(1, vec![0x00, 0xff]),
+            MvccKey::TxnWrite(2, vec![0x00]),
+            MvccKey::Version(vec![], 7),
+            MvccKey::Version(vec![0x00], 1),
+            MvccKey::Version(vec![0x00], 2),
+            MvccKey::Version(vec![0x00, 0x00], 1),
+            MvccKey::Version(vec![0x00, 0x01], 1),
+            MvccKey::Version(vec![0x01], 1),
+            MvccKey::Version(vec![0xff, 0xff], 1),
+            MvccKey::Sequence(vec![]),
+            MvccKey::Sequence(vec![0x00]),
+        ];
+        // The encoded keys must sort in the same order and decode back unchanged.
+        for (i, key) in keys.iter().enumerate() {
+            let encoded = key.encode();
+            assert_eq!(&MvccKey::decode(&encoded)?, key);
+            if let Some(next) = keys.get(i + 1) {
+                assert!(encoded < next.encode(), "{:?} < {:?}", key, next);
+            }
+        }
+
+        assert_eq!(
+            decode_bytes(&encode_bytes(b"a\0b")),
+            Some((b"a\0b".to_vec(), &[][..]))
+        );
+        assert_eq!(decode_bytes(&[0x01, 0x00]), None);
+        assert!(MvccKey::decode(&[5]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_dirty_read() -> Result<()> {
+        let mvcc = Mvcc::new(MemoryEngine::new());
+        let txn1 = mvcc.begin()?;
+        let txn2 = mvcc.begin()?;
+
+        txn1.set(b"a".to_vec(), b"1".to_vec())?;
+        assert_eq!(txn1.get(b"a".to_vec())?, Some(b"1".to_vec()));
+        assert_eq!(txn2.get(b"a".to_vec())?, None);
+        assert!(txn2.scan_prefix(b"a".to_vec())?.is_empty());
+
+        // Committed changes are invisible to transactions that were already running.
+        txn1.commit()?;
+        assert_eq!(txn2.get(b"a".to_vec())?, None);
+        assert_eq!(mvcc.begin()?.get(b"a".to_vec())?, Some(b"1".to_vec()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_repeatable_read() -> Result<()> {
+        let mvcc = Mvcc::new(MemoryEngine::new());
+        let txn = mvcc.begin()?;
+        txn.set(b"a".to_vec(), b"1".to_vec())?;
+        txn.set(b"b".to_vec(), b"1".to_vec())?;
+        txn.commit()?;
+
+        let reader = mvcc.begin()?;
+        assert_eq!(reader.get(b"a".to_vec())?, Some(b"1".to_vec()));
+
+        let writer = mvcc.begin()?;
+        writer.set(b"a".to_vec(), b"2".to_vec())?;
+        writer.delete(b"b".to_vec())?;
+        writer.set(b"c".to_vec(), b"2".to_vec())?;
+        writer.commit()?;
+
+        assert_eq!(reader.get(b"a".to_vec())?, Some(b"1".to_vec()));
+        assert_eq!(reader.get(b"b".to_vec())?, Some(b"1".to_vec()));
+        let keys = |txn: &super::MvccTransaction<MemoryEngine>| -> Result<Vec<Vec<u8>>> {
+            Ok(txn
+                .scan_prefix(vec![])?
+                .into_iter()
+                .map(|r| r.key)
+                .collect())
+        };
+        assert_eq!(keys(&reader)?, vec![b"a".to_vec(), b"b".to_vec()]);
+        assert_eq!(keys(&mvcc.begin()?)?, vec![b"a".to_vec(), b"c".to_vec()]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_conflict() -> Result<()> {
+        let mvcc = Mvcc::new(MemoryEngine::new());
+        let txn1 = mvcc.begin()?;
+        let txn2 = mvcc.begin()?;
+        let txn3 = mvcc.begin()?;
+
+        txn1.set(b"a".to_vec(), b"1".to_vec())?;
+        assert_eq!(
+            txn2.set(b"a".to_vec(), b"2".to_vec()),
+            Err(Error::WriteConflict)
+        );
+        txn1.commit()?;
+        // Still a conflict: txn3 began before txn1 committed.
+        assert_eq!(txn3.delete(b"a".to_vec()), Err(Error::WriteConflict));
+
+        let txn4 = mvcc.begin()?;
+        txn4.set(b"a".to_vec(), b"4".to_vec())?;
+        txn4.commit()?;
+        assert_eq!(mvcc.begin()?.get(b"a".to_vec())?, Some(b"4".to_vec()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_rollback() -> Result<()> {
+        let mvcc = Mvcc::new(MemoryEngine::new());
+        let txn = mvcc.begin()?;
+        txn.set(b"a".to_vec(), b"1".to_vec())?;
+        txn.commit()?;
+
+        let txn = mvcc.begin()?;
+        txn.set(b"a".to_vec(), b"2".to_vec())?;
+        txn.set(b"b".to_vec(), b"2".to_vec())?;
+        txn.rollback()?;
+        assert_eq!(mvcc.begin()?.get(b"a".to_vec())?, Some(b"1".to_vec()));
+        assert_eq!(mvcc.begin()?.get(b"b".to_vec())?, None);
+
+        // Dropping a transaction without committing rolls it back, so no conflict remains.
+        mvcc.begin()?.set(b"a".to_vec(), b"3".to_vec())?;
+        let txn = mvcc.begin()?;
+        txn.set(b"a".to_vec(), b"4".to_vec())?;
+        txn.commit()?;
+        assert_eq!(mvcc.begin()?.get(b"a".to_vec())?, Some(b"4".to_vec()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_counter() -> Result<()> {
+        let mvcc = Mvcc::new(MemoryEngine::new());
+        let txn1 = mvcc.begin()?;
+        let txn2 = mvcc.begin()?;
+        txn1.adjust_counter(b"c".to_vec(), 3);
+        txn2.adjust_counter(b"c".to_vec(), 2);
+        assert_eq!(txn1.counter(b"c".to_vec())?, 3);
+        assert_eq!(txn2.counter(b"c".to_vec())?, 2);
+        txn1.commit()?;
+        txn2.commit()?;
+        assert_eq!(mvcc.begin()?.counter(b"c".to_vec())?, 5);
+
+        let txn = mvcc.begin()?;
+        txn.adjust_counter(b"c".to_vec(), -4);
+        txn.rollback()?;
+        assert_eq!(mvcc.begin()?.counter(b"c".to_vec())?, 5);
+        Ok(())
+    }
+
+    #[test]
+    fn test_recover_active_transactions() -> Result<()> {
+        let dir = std::env::temp_dir().join("sqldb-rs-test-mvcc-recover");
+        let path = dir.join("sqldb-log");
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir)?;
+        }
+
+        let mvcc = Mvcc::new(DiskEngine::new(path.clone())?);
+        let txn = mvcc.begin()?;
+        txn.set(b"a".to_vec(), b"1".to_vec())?;
+        txn.commit()?;
+        drop(txn);
+        // Simulates a crash: the transaction is neither committed nor rolled back.
+        let txn = mvcc.begin()?;
+        txn.set(b"a".to_vec(), b"2".to_vec())?;
+        txn.set(b"b".to_vec(), b"2".to_vec())?;
+        txn.finished.set(true);
+        drop(txn);
+        drop(mvcc);
+
+        let mvcc = Mvcc::new(DiskEngine::new(path)?);
+        let txn = mvcc.begin()?;
+        assert_eq!(txn.get(b"a".to_vec())?, Some(b"1".to_vec()));
+        assert_eq!(txn.get(b"b".to_vec())?, None);
+        txn.set(b"a".to_vec(), b"3".to_vec())?;
+        txn.set(b"b".to_vec(), b"3".to_vec())?;
+        txn.commit()?;
+        assert_eq!(mvcc.begin()?.get(b"a".to_vec())?, Some(b"3".to_vec()));
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}